@@ -0,0 +1,70 @@
+// Connection to the host and the settings round trip shared by every property inspector.
+// A page lists its fields with their defaults and calls inspector() at the end of its body.
+
+let websocket = null;
+let uuid = null;
+let action = null;
+let settings = {};
+let page = { fields: {}, show() {}, connected() {}, message() {} };
+
+function send(event, payload) {
+	websocket.send(JSON.stringify({ event, action, context: uuid, payload }));
+}
+
+// The plugin reads every setting as a string, so checkboxes are saved as "true" or "false"
+function save() {
+	for (const [id, fallback] of Object.entries(page.fields)) {
+		const element = document.getElementById(id);
+		settings[id] = typeof fallback === "boolean" ? String(element.checked) : element.value;
+	}
+	send("setSettings", settings);
+}
+
+function showSettings() {
+	for (const [id, fallback] of Object.entries(page.fields)) {
+		const element = document.getElementById(id);
+		const value = settings[id];
+		if (typeof fallback === "boolean") {
+			element.checked = value === undefined ? fallback : value === "true";
+		} else {
+			element.value = value || fallback;
+		}
+	}
+	page.show();
+}
+
+// `fields` maps element IDs to their defaults: a boolean for checkboxes, a string otherwise.
+// `hooks` may add `show()` after settings are shown, `connected()` once registered with the host,
+// and `message(data)` for anything other than new settings.
+function inspector(fields, hooks = {}) {
+	page = { ...page, ...hooks, fields };
+	for (const id of Object.keys(fields)) {
+		document.getElementById(id).addEventListener("change", save);
+	}
+}
+
+function connectElgatoStreamDeckSocket(port, propertyInspectorUUID, registerEvent, info, actionInfo) {
+	uuid = propertyInspectorUUID;
+	const parsed = JSON.parse(actionInfo);
+	action = parsed.action;
+	settings = parsed.payload.settings || {};
+	showSettings();
+
+	websocket = new WebSocket("ws://localhost:" + port);
+	websocket.onopen = () => {
+		websocket.send(JSON.stringify({ event: registerEvent, uuid }));
+		page.connected();
+	};
+	websocket.onmessage = (message) => {
+		const data = JSON.parse(message.data);
+		if (data.event === "didReceiveSettings") {
+			settings = data.payload.settings || {};
+			showSettings();
+		} else {
+			page.message(data);
+		}
+	};
+}
+function connectOpenActionSocket(...args) {
+	connectElgatoStreamDeckSocket(...args);
+}
@@ -1,322 +1,1427 @@
-use super::{call_mpris_method, update_all, fetch_and_convert_to_data_url, get_album_art_for_sink_input, ENCODER_PRESSED, DIAL_STATES};
+use super::{call_mpris_method, seek_active_player, get_track_progress, update_all, get_album_art_for_sink_input, DIAL_STATES, DIAL_SETTINGS, LAST_ROTATIONS, DIAL_PRESSES, NOW_PLAYING, PLAY_PAUSE_TITLES, SCROLL_OFFSET};
+use super::apps::APPS;
+use super::art;
+use super::icons::{self, theme_icon_data_url};
+use super::audio::{AUDIO, AudioBackend, DEFAULT_SINK, DEFAULT_SOURCE, Device, SinkInput, volume_percent};
+
+use base64::{Engine as _, engine::general_purpose};
+use serde_json::json;
 
 use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
 use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
 
 use openaction::*;
 
-/// Updates the dial image based on the currently selected sink input
-pub async fn update_dial_image_for_selected_sink(instance: &Instance) -> OpenActionResult<()> {
-	// Get the selected sink input for this instance
-	let selected = {
-		let states = DIAL_STATES.lock().unwrap();
-		states.get(&instance.instance_id).map(|(_, sink)| *sink).unwrap_or(0)
+/// What a volume dial controls
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DialTarget {
+	#[default]
+	Master,
+	/// Every stream of one application, keyed by [`SinkInput::app_id`]
+	App(String),
+	/// A single stream, when the dial doesn't group streams by application
+	Stream(u32),
+	/// One output device by name
+	Sink(String),
+	/// One input device by name
+	Source(String),
+}
+
+/// Instance settings key holding the persisted [`DialTarget`]
+pub const TARGET_SETTING: &str = "target";
+
+impl DialTarget {
+	/// Parse a persisted target, `master`, `app:<app id>`, `sink:<name>` or `source:<name>`
+	pub fn from_setting(value: &str) -> Option<DialTarget> {
+		if value == "master" {
+			return Some(DialTarget::Master);
+		}
+		let (kind, name) = value.split_once(':')?;
+		if name.is_empty() {
+			return None;
+		}
+		match kind {
+			"app" => Some(DialTarget::App(name.to_owned())),
+			"sink" => Some(DialTarget::Sink(name.to_owned())),
+			"source" => Some(DialTarget::Source(name.to_owned())),
+			_ => None,
+		}
+	}
+
+	/// The setting for this target. Stream indices don't survive a restart, so save
+	/// [`DialTarget::persisted`] instead of a [`DialTarget::Stream`].
+	pub fn to_setting(&self) -> String {
+		match self {
+			DialTarget::Master => "master".to_owned(),
+			DialTarget::App(app_id) => format!("app:{}", app_id),
+			DialTarget::Stream(index) => format!("stream:{}", index),
+			DialTarget::Sink(name) => format!("sink:{}", name),
+			DialTarget::Source(name) => format!("source:{}", name),
+		}
+	}
+
+	/// The target to save: a single stream is remembered as its app, or master once it is gone
+	pub fn persisted(&self, sink_inputs: &[SinkInput]) -> DialTarget {
+		match self {
+			DialTarget::Stream(index) => sink_inputs
+				.iter()
+				.find(|sink_input| sink_input.index == *index)
+				.map_or(DialTarget::Master, |sink_input| DialTarget::App(sink_input.app_id())),
+			target => target.clone(),
+		}
+	}
+
+	/// Devices stop at 100% unless configured otherwise; apps may be boosted like in pavucontrol
+	fn default_limit(&self) -> Option<u32> {
+		match self {
+			DialTarget::App(_) | DialTarget::Stream(_) => None,
+			_ => Some(100),
+		}
+	}
+}
+
+/// How dial steps map onto the volume
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VolumeCurve {
+	/// Every step changes the volume by the same percentage
+	#[default]
+	Linear,
+	/// Steps are even in the cube root of the volume percentage, so they get finer towards
+	/// silence and coarser towards 100%. This is on top of the server's own cubic volume scale.
+	Cubic,
+}
+
+/// Rotations closer together than this count as a spin
+const FAST_ROTATION: Duration = Duration::from_millis(80);
+/// Rotations further apart than this count as a slow, deliberate turn
+const SLOW_ROTATION: Duration = Duration::from_millis(300);
+
+/// Presses released before this are taps
+const TAP_DURATION: Duration = Duration::from_millis(500);
+
+/// An encoder that is being held down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DialPress {
+	pub pressed_at: Instant,
+	/// Whether the encoder was rotated while held
+	pub rotated: bool,
+}
+
+impl DialPress {
+	/// A short press without rotating in between
+	pub fn is_tap(&self) -> bool {
+		!self.rotated && self.pressed_at.elapsed() < TAP_DURATION
+	}
+}
+
+/// Every encoder that is currently held down, per instance
+#[derive(Debug, Default)]
+pub struct DialPresses {
+	presses: HashMap<String, DialPress>,
+	/// Whether any encoder has sent dial_up yet; stock firmware only sends dial_down
+	release_seen: bool,
+}
+
+impl DialPresses {
+	fn press(&mut self, instance_id: &str) -> Instant {
+		let press = DialPress { pressed_at: Instant::now(), rotated: false };
+		self.presses.insert(instance_id.to_owned(), press);
+		press.pressed_at
+	}
+
+	fn release(&mut self, instance_id: &str) -> Option<DialPress> {
+		self.presses.remove(instance_id)
+	}
+
+	/// Remember that the hardware sends dial_up
+	fn saw_release(&mut self) {
+		self.release_seen = true;
+	}
+
+	/// Whether this encoder is held down; if so, remember that it was rotated
+	fn rotate_pressed(&mut self, instance_id: &str) -> bool {
+		match self.presses.get_mut(instance_id) {
+			Some(press) => {
+				press.rotated = true;
+				true
+			}
+			None => false,
+		}
+	}
+
+	/// Whether the encoder is held during this rotation. A held encoder is marked as rotated.
+	fn held(&mut self, instance_id: &str, pressed: bool, source: PressSource) -> bool {
+		match source {
+			PressSource::Events => self.rotate_pressed(instance_id),
+			PressSource::Flag => {
+				// Cancels the mute pending from the press; the press itself may never be released
+				if pressed {
+					self.rotate_pressed(instance_id);
+				}
+				pressed
+			}
+			PressSource::Auto => {
+				let held_by_events = self.rotate_pressed(instance_id);
+				// Stock firmware sends dial_down without ever releasing, which would leave the dial cycling for good
+				pressed || (held_by_events && self.release_seen)
+			}
+		}
+	}
+
+	/// Settle the press made at `pressed_at`, unless a newer one replaced it. True if the encoder
+	/// wasn't rotated while held, so the press counts as a tap.
+	fn settle(&mut self, instance_id: &str, pressed_at: Instant) -> bool {
+		match self.presses.get(instance_id) {
+			Some(press) if press.pressed_at == pressed_at => {
+				let tapped = !press.rotated;
+				self.presses.remove(instance_id);
+				tapped
+			}
+			_ => false,
+		}
+	}
+}
+
+fn press_dial(instance_id: &str) -> Instant {
+	DIAL_PRESSES.lock().unwrap().press(instance_id)
+}
+
+/// The encoder was let go; this also shows [`PressSource::Auto`] that releases arrive
+fn release_dial(instance_id: &str) -> Option<DialPress> {
+	let mut presses = DIAL_PRESSES.lock().unwrap();
+	presses.saw_release();
+	presses.release(instance_id)
+}
+
+fn rotate_pressed_dial(instance_id: &str) -> bool {
+	DIAL_PRESSES.lock().unwrap().rotate_pressed(instance_id)
+}
+
+/// Where a dial learns that its encoder is held down
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PressSource {
+	/// Trust the `pressed` flag, and press events once the hardware has shown it sends releases
+	#[default]
+	Auto,
+	/// dial_down/dial_up events, as sent by the modified opendeck-akp05 plugin
+	Events,
+	/// The `pressed` flag of each rotation, for stock device plugins
+	Flag,
+}
+
+fn dial_held(instance_id: &str, pressed: bool, source: PressSource) -> bool {
+	DIAL_PRESSES.lock().unwrap().held(instance_id, pressed, source)
+}
+
+fn settle_press(instance_id: &str, pressed_at: Instant) -> bool {
+	DIAL_PRESSES.lock().unwrap().settle(instance_id, pressed_at)
+}
+
+/// Start a press on an encoder whose taps run `tap`. Without release events there is no telling
+/// a tap from a hold, so with the pressed flag `tap` runs once the press had time to turn into
+/// press + rotate and didn't.
+fn begin_press<F, Fut>(instance: &Instance, source: PressSource, tap: F)
+where
+	F: FnOnce(Arc<Instance>) -> Fut + Send + 'static,
+	Fut: Future<Output = OpenActionResult<()>> + Send,
+{
+	let pressed_at = press_dial(&instance.instance_id);
+	if source != PressSource::Flag {
+		return;
+	}
+	let instance_id = instance.instance_id.clone();
+	tokio::spawn(async move {
+		tokio::time::sleep(TAP_DURATION).await;
+		if !settle_press(&instance_id, pressed_at) {
+			return;
+		}
+		if let Some(instance) = get_instance(instance_id).await {
+			tap(instance).await.unwrap_or_else(|e| {
+				log::error!("Failed to handle dial tap: {}", e);
+			});
+		}
+	});
+}
+
+/// End a press, returning whether it was a tap. With the pressed flag the press is settled by
+/// the tap [`begin_press`] scheduled instead.
+fn end_press(instance_id: &str, source: PressSource) -> bool {
+	if source == PressSource::Flag {
+		DIAL_PRESSES.lock().unwrap().saw_release();
+		return false;
+	}
+	let press = release_dial(instance_id);
+	log::info!("Dial {} released after {:?}", instance_id, press.map(|press| press.pressed_at.elapsed()));
+	press.is_some_and(|press| press.is_tap())
+}
+
+/// Volume dial options as edited in the property inspector
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialSettings {
+	/// Target pinned in the inspector, if any
+	pub target: Option<DialTarget>,
+	/// Volume change per tick, in percent
+	pub step: u32,
+	/// Highest volume the dial may set, in percent
+	pub limit: Option<u32>,
+	/// Whether press + rotate cycles through targets
+	pub cycle: bool,
+	/// Slow turns take 1% steps and fast spins bigger ones
+	pub acceleration: bool,
+	pub curve: VolumeCurve,
+	pub press_source: PressSource,
+	pub filter: CycleFilter,
+}
+
+impl DialSettings {
+	/// Read the instance settings, falling back to defaults for missing or malformed values
+	pub fn from_settings(settings: &HashMap<String, String>) -> DialSettings {
+		let number = |key: &str| settings.get(key).and_then(|value| value.trim().parse::<u32>().ok());
+		DialSettings {
+			target: settings.get(TARGET_SETTING).and_then(|value| DialTarget::from_setting(value)),
+			step: number("step").filter(|step| (1..=100).contains(step)).unwrap_or(5),
+			limit: number("limit").filter(|limit| *limit > 0),
+			cycle: settings.get("cycle").is_none_or(|value| value != "false"),
+			acceleration: settings.get("acceleration").is_some_and(|value| value == "true"),
+			curve: match settings.get("curve").map(String::as_str) {
+				Some("cubic") => VolumeCurve::Cubic,
+				_ => VolumeCurve::Linear,
+			},
+			press_source: match settings.get("pressSource").map(String::as_str) {
+				Some("events") => PressSource::Events,
+				Some("flag") => PressSource::Flag,
+				_ => PressSource::Auto,
+			},
+			filter: CycleFilter {
+				include: setting_list(settings, "include"),
+				exclude: setting_list(settings, "exclude"),
+				order: setting_list(settings, "order"),
+				skip_idle: settings.get("skipIdle").is_some_and(|value| value == "true"),
+				skip_system_sounds: settings.get("skipSystemSounds").is_none_or(|value| value != "false"),
+				split_streams: settings.get("group").is_some_and(|value| value == "false"),
+			},
+		}
+	}
+
+	/// The volume change in percent for `ticks`, given the time since the previous rotation
+	pub fn rotation_delta(&self, ticks: i16, since_last: Option<Duration>) -> i32 {
+		let step = if !self.acceleration {
+			self.step
+		} else {
+			match since_last {
+				Some(interval) if interval < FAST_ROTATION => self.step * 3,
+				Some(interval) if interval < SLOW_ROTATION => self.step,
+				// Several ticks in one event means the dial is spinning too
+				_ if ticks.abs() > 1 => self.step,
+				_ => 1,
+			}
+		};
+		ticks as i32 * step as i32
+	}
+
+	/// The volume limit for `target`, honouring the configured one
+	pub fn limit_for(&self, target: &DialTarget) -> Option<u32> {
+		self.limit.or(target.default_limit())
+	}
+}
+
+/// The dial target of an instance, master volume if it never picked one
+pub fn dial_target(instance_id: &str) -> DialTarget {
+	DIAL_STATES.lock().unwrap().get(instance_id).cloned().unwrap_or_default()
+}
+
+/// The target a dial saves in its settings; see [`DialTarget::persisted`]
+async fn persisted_target(target: &DialTarget) -> DialTarget {
+	match target {
+		DialTarget::Stream(_) => target.persisted(&AUDIO.sink_inputs().await.unwrap_or_default()),
+		_ => target.clone(),
+	}
+}
+
+/// Keep a volume dial's settings, so [`save_dial_target`] doesn't drop the other options
+fn remember_settings(instance_id: &str, settings: &HashMap<String, String>) {
+	DIAL_SETTINGS.lock().unwrap().insert(instance_id.to_owned(), settings.clone());
+}
+
+/// Persist a volume dial's target so it survives restarts. Does nothing for a dial whose
+/// settings were never seen.
+pub async fn save_dial_target(instance: &Instance, target: &DialTarget) -> OpenActionResult<()> {
+	let saved = persisted_target(target).await.to_setting();
+	let settings = match DIAL_SETTINGS.lock().unwrap().get_mut(&instance.instance_id) {
+		Some(settings) => {
+			settings.insert(TARGET_SETTING.to_owned(), saved);
+			settings.clone()
+		}
+		None => return Ok(()),
 	};
-	log::info!("Updating dial image for selected sink input ID: {}, instance: {:?}", selected, instance.instance_id);
-	if selected == 0 {
-		// Master volume - set to volume icon
-		let image_path = "icons/volume.png";
-		log::info!("Setting master volume icon: {}", image_path);
-		if let Ok(abs_path) = std::fs::canonicalize(image_path) {
-			let file_url = format!("file://{}", abs_path.display());
-			match fetch_and_convert_to_data_url(&file_url).await {
-				Ok(data_url) => {
-					log::info!("Converted to data URL (length: {})", data_url.len());
-					if let Err(e) = instance.set_image(Some(data_url), None).await {
-						log::error!("Failed to set master volume icon: {}", e);
+	instance.set_settings(&settings).await
+}
+
+/// The streams a dial target controls: every stream of an app, or a single stream
+pub fn target_streams<'a>(sink_inputs: &'a [SinkInput], target: &DialTarget) -> Vec<&'a SinkInput> {
+	match target {
+		DialTarget::App(app_id) => sink_inputs.iter().filter(|sink_input| sink_input.app_id() == *app_id).collect(),
+		DialTarget::Stream(index) => sink_inputs.iter().filter(|sink_input| sink_input.index == *index).collect(),
+		_ => Vec::new(),
+	}
+}
+
+/// All current streams belonging to `app_id`
+pub fn app_streams<'a>(sink_inputs: &'a [SinkInput], app_id: &'a str) -> impl Iterator<Item = &'a SinkInput> {
+	sink_inputs.iter().filter(move |sink_input| sink_input.app_id() == app_id)
+}
+
+/// Dim an image and put a crossed-out speaker on it. Images that can't be decoded are left alone.
+pub fn with_mute_overlay(data_url: &str) -> String {
+	let overlaid = data_url
+		.split_once(";base64,")
+		.and_then(|(_, data)| general_purpose::STANDARD.decode(data).ok())
+		.map(|bytes| art::mute_overlay(&bytes, art::ART_OPTIONS.size));
+	match overlaid {
+		Some(Ok(png)) => format!("data:image/png;base64,{}", general_purpose::STANDARD.encode(png)),
+		_ => {
+			log::warn!("Failed to draw the mute overlay");
+			data_url.to_owned()
+		}
+	}
+}
+
+/// Show `data_url` on the dial, marked as muted if needed
+async fn set_dial_image(instance: &Instance, data_url: String, muted: bool) -> OpenActionResult<()> {
+	let image = if muted {
+		let original = data_url.clone();
+		tokio::task::spawn_blocking(move || with_mute_overlay(&data_url)).await.unwrap_or(original)
+	} else {
+		data_url
+	};
+	instance.set_image(Some(image), None).await
+}
+
+/// Icon for master volume or a pinned device: the output's headphones or speaker, or the microphone
+async fn device_icon(target: &DialTarget) -> &'static str {
+	match target {
+		DialTarget::Sink(name) => AUDIO.sink(name).await.map_or("icons/speaker.svg", |sink| sink_icon(&sink)),
+		DialTarget::Source(_) => "icons/mic.svg",
+		_ => "icons/volume.png",
+	}
+}
+
+/// Updates the dial image based on the currently selected target
+pub async fn update_dial_image_for_selected_sink(instance: &Instance) -> OpenActionResult<()> {
+	let target = dial_target(&instance.instance_id);
+	log::info!("Updating dial image for {:?}, instance: {:?}", target, instance.instance_id);
+	let status = selected_status(AUDIO.as_ref(), &target).await.unwrap_or_else(|e| {
+		log::warn!("Failed to read state of {:?}: {}", target, e);
+		DialStatus::unavailable(&target)
+	});
+	show_dial_feedback(instance, &status).await?;
+	let muted = status.muted;
+	match target {
+		DialTarget::App(_) | DialTarget::Stream(_) => {}
+		DialTarget::Master | DialTarget::Sink(_) | DialTarget::Source(_) => {
+			let image_path = device_icon(&target).await;
+			log::info!("Setting icon for {:?}: {}", target, image_path);
+			match icon_data_url(image_path).await {
+				Some(data_url) => {
+					if let Err(e) = set_dial_image(instance, data_url, muted).await {
+						log::error!("Failed to set icon for {:?}: {}", target, e);
 					} else {
-						log::info!("Successfully set master volume icon");
+						log::info!("Successfully set icon for {:?}", target);
 					}
 				}
-				Err(e) => {
-					log::error!("Failed to convert {} to data URL: {}", image_path, e);
-				}
+				None => log::error!("Failed to load {}", image_path),
 			}
-		} else {
-			log::error!("Failed to find {}", image_path);
+			return Ok(());
 		}
-		return Ok(());
 	}
 	
-	// Specific app selected - get app info
-	if let Ok(info_output) = std::process::Command::new("pactl")
-		.args(&["list", "sink-inputs"])
-		.output()
+	// Specific app selected - use its playing stream if it has one. The app may also have
+	// no streams right now, in which case only its icon can be shown.
+	let sink_inputs = AUDIO.sink_inputs().await.unwrap_or_else(|e| {
+		log::error!("Failed to list sink inputs: {}", e);
+		Vec::new()
+	});
+	let streams = target_streams(&sink_inputs, &target);
+	let stream = streams.iter().find(|sink_input| !sink_input.corked).or(streams.first());
+	let fallback_name = match &target {
+		DialTarget::App(app_id) => app_id.as_str(),
+		_ => "unknown",
+	};
+	let (selected, app_name, process_binary, app_id) = match stream {
+		Some(sink_input) => (Some(sink_input.index), sink_input.app_name(), sink_input.process_binary(), sink_input.app_id()),
+		None => (None, fallback_name, fallback_name, fallback_name.to_owned()),
+	};
+	
+	let app_lower = app_name.to_lowercase();
+	let process_lower = process_binary.to_lowercase();
+	
+	// Media players and browsers that might have metadata are listed in apps.json
+	let config = APPS.lookup(&[process_binary, &app_id, app_name]).unwrap_or_default();
+	
+	let mut image_set = false;
+	
+	if let Some(selected) = selected
+		&& config.mpris
 	{
-		let info = String::from_utf8_lossy(&info_output.stdout);
-		let lines = info.lines().skip_while(|line| !line.contains(&format!("Sink Input #{}", selected)));
+		log::info!("Attempting to fetch album art for media application: {} [{}], sink input: {}", app_name, process_binary, selected);
 		
-		let app_name = lines.clone()
-			.find(|line| line.contains("application.name"))
-			.and_then(|line| line.split('"').nth(1))
-			.unwrap_or("Unknown");
+		// The bus name may differ from the binary (chrome registers as chromium), but the original
+		// name is still used for sink input filtering
+		let mpris_name = config.bus_name.as_deref();
 		
-		let process_binary = lines.clone()
-			.find(|line| line.contains("application.process.binary"))
-			.and_then(|line| line.split('"').nth(1))
-			.unwrap_or("");
+		if let Some(album_art) = get_album_art_for_sink_input(selected, process_binary, mpris_name).await {
+			if let Err(e) = set_dial_image(instance, album_art, muted).await {
+				log::warn!("Failed to set album art: {}", e);
+			} else {
+				log::info!("Successfully set matched album art");
+				image_set = true;
+			}
+		}
+	}
+	
+	if !image_set {
+		// Try the configured icon first, then find one by process name
+		let possible_names = vec![
+			process_binary,
+			&app_lower,
+			&process_lower,
+		];
 		
-		let app_lower = app_name.to_lowercase();
-		let process_lower = process_binary.to_lowercase();
+		log::info!("Looking for icon matching: {:?}", possible_names);
 		
-		// Check if it's a media player or browser that might have metadata
-		let is_media_app = app_lower.contains("firefox") || app_lower.contains("chrome") 
-			|| app_lower.contains("brave") || app_lower.contains("spotify")
-			|| app_lower.contains("vlc") || process_lower.contains("mpv");
+		let icon_paths = config.icon_path().into_iter().chain(
+			possible_names
+				.into_iter()
+				.filter(|name| !name.is_empty())
+				.flat_map(|name| ["svg", "png", "jpg", "jpeg"].map(|ext| format!("icons/{}.{}", name, ext))),
+		);
 		
-		let mut image_set = false;
+		for icon_path in icon_paths {
+			// Check if file exists in plugin directory
+			if std::path::Path::new(&icon_path).exists() {
+				log::info!("Found icon: {}", icon_path);
+				match icon_data_url(&icon_path).await {
+					Some(data_url) => {
+						if let Err(e) = set_dial_image(instance, data_url, muted).await {
+							log::warn!("Failed to set icon: {}", e);
+						} else {
+							log::info!("Successfully set icon");
+						}
+					}
+					None => log::warn!("Failed to load {}", icon_path),
+				}
+				image_set = true;
+				break;
+			}
+		}
 		
-		if is_media_app {
-			log::info!("Attempting to fetch album art for media application: {} [{}], sink input: {}", app_name, process_binary, selected);
-			
-			// Map chrome to chromium for MPRIS lookup, but keep original name for sink input filtering
-			let mpris_name = if process_binary == "chrome" {
-				Some("chromium")
+		// Bundled icons override the desktop's own
+		if !image_set
+			&& let Some(data_url) = {
+				let (stream, app_id) = (stream.copied().cloned(), app_id.clone());
+				tokio::task::spawn_blocking(move || theme_icon_data_url(stream.as_ref(), &app_id)).await.ok().flatten()
+			}
+		{
+			if let Err(e) = set_dial_image(instance, data_url, muted).await {
+				log::warn!("Failed to set theme icon: {}", e);
 			} else {
-				None
-			};
-			
-			if let Some(album_art) = get_album_art_for_sink_input(selected, process_binary, mpris_name).await {
-				if let Err(e) = instance.set_image(Some(album_art), None).await {
-					log::warn!("Failed to set album art: {}", e);
-				} else {
-					log::info!("Successfully set matched album art");
-					image_set = true;
-				}
+				log::info!("Successfully set theme icon");
 			}
+			image_set = true;
 		}
 		
 		if !image_set {
-			// Try to find icon by process name
-			let possible_names = vec![
-				process_binary,
-				&app_lower,
-				&process_lower,
-			];
-			
-			log::info!("Looking for icon matching: {:?}", possible_names);
-			
-			for name in possible_names {
-				if name.is_empty() { continue; }
-				
-				for ext in &["svg", "png", "jpg", "jpeg"] {
-					let icon_path = format!("icons/{}.{}", name, ext);
-					// Check if file exists in plugin directory
-					if std::path::Path::new(&icon_path).exists() {
-						log::info!("Found icon: {}", icon_path);
-						if let Ok(abs_path) = std::fs::canonicalize(&icon_path) {
-							let file_url = format!("file://{}", abs_path.display());
-							match fetch_and_convert_to_data_url(&file_url).await {
-								Ok(data_url) => {
-									log::info!("Converted to data URL (length: {})", data_url.len());
-									if let Err(e) = instance.set_image(Some(data_url), None).await {
-										log::warn!("Failed to set icon: {}", e);
-									} else {
-										log::info!("Successfully set icon");
-									}
-								}
-								Err(e) => {
-									log::warn!("Failed to convert {} to data URL: {}", icon_path, e);
-								}
-							}
-						}
-						image_set = true;
-						break;
+			log::warn!("No icon found for app: {} [{}], using unknown.png", app_name, process_binary);
+			// Use unknown.png as fallback
+			let fallback_path = "icons/unknown.png";
+			log::info!("Setting fallback unknown icon: {}", fallback_path);
+			match icon_data_url(fallback_path).await {
+				Some(data_url) => {
+					if let Err(e) = set_dial_image(instance, data_url, muted).await {
+						log::error!("Failed to set unknown icon: {}", e);
+					} else {
+						log::info!("Successfully set unknown icon");
 					}
 				}
-				if image_set { break; }
+				None => log::error!("Failed to load {}", fallback_path),
 			}
+		}
+	}
+	
+	Ok(())
+}
+
+/// One entry on from `current` in the direction of `ticks`, wrapping around at both ends
+pub fn step_index(current: usize, total: usize, ticks: i16) -> usize {
+	if ticks > 0 {
+		(current + 1) % total
+	} else if current == 0 {
+		total - 1
+	} else {
+		current - 1
+	}
+}
+
+/// Which apps a dial cycles through, and in what order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CycleFilter {
+	/// Only these apps, if any are listed
+	pub include: Vec<String>,
+	pub exclude: Vec<String>,
+	/// Entries pinned to the front in this order; `master` may be among them
+	pub order: Vec<String>,
+	/// Leave out streams that are paused
+	pub skip_idle: bool,
+	/// Leave out event sounds and notifications
+	pub skip_system_sounds: bool,
+	/// Offer every stream on its own instead of one entry per app
+	pub split_streams: bool,
+}
+
+impl CycleFilter {
+	/// Whether a stream's app should be offered
+	fn accepts(&self, sink_input: &SinkInput) -> bool {
+		let role = sink_input.property("media.role").unwrap_or("");
+		if self.skip_system_sounds && (role == "event" || role == "notification") {
+			return false;
+		}
+		if self.skip_idle && sink_input.corked {
+			return false;
+		}
+		(self.include.is_empty() || self.include.iter().any(|entry| app_matches(sink_input, entry)))
+			&& !self.exclude.iter().any(|entry| app_matches(sink_input, entry))
+	}
+}
+
+/// Whether a list entry names this stream's app, by app ID, process or name, ignoring case
+fn app_matches(sink_input: &SinkInput, entry: &str) -> bool {
+	sink_input.app_id() == entry
+		|| sink_input.process_binary().to_lowercase() == entry
+		|| sink_input.app_name().to_lowercase() == entry
+}
+
+/// Split a comma-separated setting into lowercase entries
+fn setting_list(settings: &HashMap<String, String>, key: &str) -> Vec<String> {
+	settings
+		.get(key)
+		.map(|value| {
+			value
+				.split(',')
+				.map(|entry| entry.trim().to_lowercase())
+				.filter(|entry| !entry.is_empty())
+				.collect()
+		})
+		.unwrap_or_default()
+}
+
+/// The entries a dial cycles through: master volume, then every accepted app in server order,
+/// with the pinned entries moved to the front
+pub fn cycle_targets(sink_inputs: &[SinkInput], filter: &CycleFilter) -> Vec<DialTarget> {
+	let mut targets = vec![DialTarget::Master];
+	for sink_input in sink_inputs.iter().filter(|sink_input| filter.accepts(sink_input)) {
+		let target = if filter.split_streams {
+			DialTarget::Stream(sink_input.index)
+		} else {
+			DialTarget::App(sink_input.app_id())
+		};
+		if !targets.contains(&target) {
+			targets.push(target);
+		}
+	}
+
+	let mut pinned = Vec::new();
+	if !filter.order.iter().any(|entry| entry == "master") {
+		pinned.push(DialTarget::Master);
+	}
+	for entry in &filter.order {
+		let found = targets.iter().filter(|target| match target {
+			DialTarget::Master => entry == "master",
+			DialTarget::App(app_id) => {
+				app_id == entry || app_streams(sink_inputs, app_id).any(|sink_input| app_matches(sink_input, entry))
+			}
+			DialTarget::Stream(_) => target_streams(sink_inputs, target)
+				.iter()
+				.any(|sink_input| app_matches(sink_input, entry)),
+			_ => false,
+		});
+		for target in found {
+			if !pinned.contains(target) {
+				pinned.push(target.clone());
+			}
+		}
+	}
+	targets.retain(|target| !pinned.contains(target));
+	pinned.extend(targets);
+	pinned
+}
+
+/// Step a dial's selection through master volume and every application.
+/// A target whose app is gone counts as master, so the next step lands on the first app.
+pub async fn cycle_selection(
+	audio: &dyn AudioBackend,
+	current: &DialTarget,
+	ticks: i16,
+	filter: &CycleFilter,
+) -> anyhow::Result<DialTarget> {
+	let sink_inputs = audio.sink_inputs().await?;
+	let targets = cycle_targets(&sink_inputs, filter);
+	let total_items = targets.len();
+	let current_index = targets
+		.iter()
+		.position(|target| target == current)
+		.or_else(|| targets.iter().position(|target| *target == DialTarget::Master))
+		.unwrap_or(0);
+	let new_index = step_index(current_index, total_items, ticks);
+	
+	let target = targets[new_index].clone();
+	match &target {
+		DialTarget::Master => log::info!("Switched to: Master Volume (1 of {})", total_items),
+		DialTarget::App(app_id) => {
+			let streams: Vec<u32> = app_streams(&sink_inputs, app_id).map(|sink_input| sink_input.index).collect();
+			log::info!("Switched to audio app: {} (streams: {:?}, {} of {})", app_id, streams, new_index + 1, total_items);
+		}
+		DialTarget::Stream(index) => log::info!("Switched to sink input {} ({} of {})", index, new_index + 1, total_items),
+		// Devices are pinned in the property inspector, never offered while cycling
+		DialTarget::Sink(_) | DialTarget::Source(_) => anyhow::bail!("Can't cycle to {:?}", target),
+	}
+	Ok(target)
+}
+
+/// Change the selected target's volume by `delta` percent, returning the new volume percentage.
+/// An app's streams are all changed by the same amount, so their relative levels are kept.
+pub async fn change_selected_volume(
+	audio: &dyn AudioBackend,
+	target: &DialTarget,
+	delta: i32,
+	limit: Option<u32>,
+) -> anyhow::Result<u32> {
+	match target {
+		DialTarget::Master => audio.change_sink_volume(DEFAULT_SINK, delta, limit).await,
+		DialTarget::Sink(name) => audio.change_sink_volume(name, delta, limit).await,
+		DialTarget::Source(name) => audio.change_source_volume(name, delta, limit).await,
+		DialTarget::App(_) | DialTarget::Stream(_) => {
+			let sink_inputs = audio.sink_inputs().await?;
+			let mut volume = None;
+			for sink_input in target_streams(&sink_inputs, target) {
+				volume = Some(audio.change_sink_input_volume(sink_input.index, delta, limit).await?);
+			}
+			volume.ok_or_else(|| anyhow::anyhow!("{:?} has no streams", target))
+		}
+	}
+}
+
+/// Apply `delta` to a volume of `current` percent along the perceptual curve, as a linear change.
+/// Never returns zero for a non-zero `delta`, so a dial can always leave silence.
+pub fn cubic_delta(current: u32, delta: i32) -> i32 {
+	let position = (current as f64 / 100.0).cbrt();
+	let target = (position + delta as f64 / 100.0).max(0.0).powi(3) * 100.0;
+	let linear = (target - current as f64).round() as i32;
+	if linear == 0 { delta.signum() } else { linear }
+}
+
+/// The current volume of the selected target in percent; the loudest stream for an app
+pub async fn selected_volume(audio: &dyn AudioBackend, target: &DialTarget) -> anyhow::Result<u32> {
+	match target {
+		DialTarget::Master => Ok(volume_percent(&audio.sink(DEFAULT_SINK).await?.volume)),
+		DialTarget::Sink(name) => Ok(volume_percent(&audio.sink(name).await?.volume)),
+		DialTarget::Source(name) => Ok(volume_percent(&audio.source(name).await?.volume)),
+		DialTarget::App(_) | DialTarget::Stream(_) => {
+			let sink_inputs = audio.sink_inputs().await?;
+			target_streams(&sink_inputs, target)
+				.into_iter()
+				.map(|sink_input| volume_percent(&sink_input.volume))
+				.max()
+				.ok_or_else(|| anyhow::anyhow!("{:?} has no streams", target))
+		}
+	}
+}
+
+/// Whether the selected target is muted; an app counts as muted when all its streams are
+pub async fn selected_muted(audio: &dyn AudioBackend, target: &DialTarget) -> anyhow::Result<bool> {
+	match target {
+		DialTarget::Master => Ok(audio.sink(DEFAULT_SINK).await?.muted),
+		DialTarget::Sink(name) => Ok(audio.sink(name).await?.muted),
+		DialTarget::Source(name) => Ok(audio.source(name).await?.muted),
+		DialTarget::App(_) | DialTarget::Stream(_) => {
+			let sink_inputs = audio.sink_inputs().await?;
+			let streams = target_streams(&sink_inputs, target);
+			Ok(!streams.is_empty() && streams.iter().all(|sink_input| sink_input.muted))
+		}
+	}
+}
+
+/// Toggle mute on the selected target, returning whether it is muted now.
+/// A partly muted app gets all its streams muted.
+pub async fn toggle_selected_mute(audio: &dyn AudioBackend, target: &DialTarget) -> anyhow::Result<bool> {
+	let muted = !selected_muted(audio, target).await?;
+	match target {
+		DialTarget::Master => audio.set_sink_mute(DEFAULT_SINK, muted).await?,
+		DialTarget::Sink(name) => audio.set_sink_mute(name, muted).await?,
+		DialTarget::Source(name) => audio.set_source_mute(name, muted).await?,
+		DialTarget::App(_) | DialTarget::Stream(_) => {
+			let sink_inputs = audio.sink_inputs().await?;
+			let streams = target_streams(&sink_inputs, target);
+			if streams.is_empty() {
+				anyhow::bail!("{:?} has no streams", target);
+			}
+			for sink_input in streams {
+				audio.set_sink_input_mute(sink_input.index, muted).await?;
+			}
+		}
+	}
+	Ok(muted)
+}
+
+/// What a volume dial shows next to its image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialStatus {
+	pub name: String,
+	/// Volume in percent, unknown while an app has no streams
+	pub volume: Option<u32>,
+	pub muted: bool,
+}
+
+impl DialStatus {
+	fn unavailable(target: &DialTarget) -> DialStatus {
+		let name = match target {
+			DialTarget::Master => "Master".to_owned(),
+			DialTarget::Stream(index) => format!("Stream {}", index),
+			DialTarget::App(name) | DialTarget::Sink(name) | DialTarget::Source(name) => name.clone(),
+		};
+		DialStatus { name, volume: None, muted: false }
+	}
+
+	/// Two-line title for hosts without feedback layouts
+	pub fn title(&self) -> String {
+		format!("{}\n{}", self.name, self.value())
+	}
+
+	fn value(&self) -> String {
+		match self.volume {
+			_ if self.muted => "Muted".to_owned(),
+			Some(volume) => format!("{}%", volume),
+			None => "-".to_owned(),
+		}
+	}
+
+	/// Payload for the `$B1` feedback layout: name, percentage and a level bar greyed out while muted
+	pub fn feedback(&self) -> serde_json::Value {
+		json!({
+			"title": self.name,
+			"value": self.value(),
+			"indicator": {
+				"value": self.volume.unwrap_or(0).min(100),
+				"opacity": if self.muted { 0.4 } else { 1.0 },
+			},
+		})
+	}
+}
+
+/// Name, volume and mute state of the selected target
+pub async fn selected_status(audio: &dyn AudioBackend, target: &DialTarget) -> anyhow::Result<DialStatus> {
+	let device = match target {
+		DialTarget::Master => audio.sink(DEFAULT_SINK).await?,
+		DialTarget::Sink(name) => audio.sink(name).await?,
+		DialTarget::Source(name) => audio.source(name).await?,
+		DialTarget::App(_) | DialTarget::Stream(_) => {
+			let sink_inputs = audio.sink_inputs().await?;
+			let streams = target_streams(&sink_inputs, target);
+			let Some(first) = streams.first() else {
+				return Ok(DialStatus::unavailable(target));
+			};
+			// Single streams are told apart by their title, e.g. a browser tab's
+			let name = match (target, &first.name) {
+				(DialTarget::Stream(_), Some(title)) => format!("{}: {}", first.app_name(), title),
+				_ => first.app_name().to_owned(),
+			};
+			return Ok(DialStatus {
+				name,
+				volume: streams.iter().map(|sink_input| volume_percent(&sink_input.volume)).max(),
+				muted: streams.iter().all(|sink_input| sink_input.muted),
+			});
+		}
+	};
+	let name = match target {
+		DialTarget::Master => "Master".to_owned(),
+		_ => device.description.unwrap_or(device.name),
+	};
+	Ok(DialStatus { name, volume: Some(volume_percent(&device.volume)), muted: device.muted })
+}
+
+async fn show_dial_feedback(instance: &Instance, status: &DialStatus) -> OpenActionResult<()> {
+	instance.set_title(Some(status.title()), None).await?;
+	instance.set_feedback(&status.feedback()).await
+}
+
+/// Refresh the name, level and mute indicator of a dial without touching its image
+pub async fn update_dial_feedback(instance: &Instance) -> OpenActionResult<()> {
+	update_dial_feedback_for(instance, &dial_target(&instance.instance_id)).await
+}
+
+async fn update_dial_feedback_for(instance: &Instance, target: &DialTarget) -> OpenActionResult<()> {
+	match selected_status(AUDIO.as_ref(), target).await {
+		Ok(status) => show_dial_feedback(instance, &status).await,
+		Err(error) => {
+			log::warn!("Failed to read state of {:?}: {}", target, error);
+			show_dial_feedback(instance, &DialStatus::unavailable(target)).await
+		}
+	}
+}
+
+/// Everything the property inspector can pin a dial to, as sent with the `targets` event
+pub async fn available_targets(audio: &dyn AudioBackend) -> anyhow::Result<serde_json::Value> {
+	let sink_inputs = audio.sink_inputs().await?;
+	let mut apps: Vec<serde_json::Value> = Vec::new();
+	for sink_input in &sink_inputs {
+		let value = DialTarget::App(sink_input.app_id()).to_setting();
+		if !apps.iter().any(|app| app["value"] == value) {
+			apps.push(json!({ "value": value, "label": sink_input.app_name() }));
+		}
+	}
+	let sinks: Vec<serde_json::Value> = audio
+		.sinks()
+		.await?
+		.into_iter()
+		.map(|sink| {
+			let label = sink.description.clone().unwrap_or_else(|| sink.name.clone());
+			json!({ "value": DialTarget::Sink(sink.name).to_setting(), "label": label })
+		})
+		.collect();
+	// Monitors mirror an output and aren't useful as a volume target
+	let sources: Vec<serde_json::Value> = audio
+		.sources()
+		.await?
+		.into_iter()
+		.filter(|source| !source.name.ends_with(".monitor"))
+		.map(|source| {
+			let label = source.description.clone().unwrap_or_else(|| source.name.clone());
+			json!({ "value": DialTarget::Source(source.name).to_setting(), "label": label })
+		})
+		.collect();
+	Ok(json!({ "event": "targets", "apps": apps, "sinks": sinks, "sources": sources }))
+}
+
+async fn send_targets(instance: &Instance) -> OpenActionResult<()> {
+	match available_targets(AUDIO.as_ref()).await {
+		Ok(targets) => instance.send_to_property_inspector(targets).await,
+		Err(error) => {
+			log::error!("Failed to list targets for the property inspector: {}", error);
+			Ok(())
+		}
+	}
+}
+
+async fn toggle_mute(instance: &Instance) -> OpenActionResult<()> {
+	let target = dial_target(&instance.instance_id);
+	match toggle_selected_mute(AUDIO.as_ref(), &target).await {
+		Ok(muted) => {
+			log::info!("{} {:?}", if muted { "Muted" } else { "Unmuted" }, target);
+			update_dial_image_for_selected_sink(instance).await?;
+		}
+		Err(error) => log::error!("Failed to toggle mute of {:?}: {}", target, error),
+	}
+	Ok(())
+}
+
+pub struct VolumeDialAction;
+#[async_trait]
+impl Action for VolumeDialAction {
+	const UUID: ActionUuid = "PlayMix.volumedialaction";
+	type Settings = HashMap<String, String>;
+
+	async fn will_appear(&self, instance: &Instance, settings: &Self::Settings) -> OpenActionResult<()> {
+		remember_settings(&instance.instance_id, settings);
+		// Restore the target this dial was bound to before a restart or profile switch
+		if let Some(target) = DialSettings::from_settings(settings).target {
+			log::info!("Restoring {:?} for instance {}", target, instance.instance_id);
+			DIAL_STATES.lock().unwrap().insert(instance.instance_id.clone(), target);
+		}
+		update_all().await;
+		Ok(())
+	}
+
+	async fn did_receive_settings(&self, instance: &Instance, settings: &Self::Settings) -> OpenActionResult<()> {
+		remember_settings(&instance.instance_id, settings);
+		// The property inspector may have pinned a different target. It sends the saved target along
+		// with every other option, and a single stream is saved as its app, so only a target that
+		// differs from the saved one replaces the current.
+		let target = DialSettings::from_settings(settings).target.unwrap_or_default();
+		let current = dial_target(&instance.instance_id);
+		if persisted_target(&current).await != target {
+			log::info!("Instance {} now controls {:?}", instance.instance_id, target);
+			DIAL_STATES.lock().unwrap().insert(instance.instance_id.clone(), target);
+			update_dial_image_for_selected_sink(instance).await?;
+		}
+		Ok(())
+	}
+
+	async fn property_inspector_did_appear(&self, instance: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
+		send_targets(instance).await
+	}
+
+	async fn send_to_plugin(
+		&self,
+		instance: &Instance,
+		_: &Self::Settings,
+		payload: &serde_json::Value,
+	) -> OpenActionResult<()> {
+		match payload["event"].as_str() {
+			Some("getTargets") => send_targets(instance).await,
+			other => {
+				log::warn!("Unknown message from property inspector: {:?}", other);
+				Ok(())
+			}
+		}
+	}
+
+	async fn dial_rotate(
+		&self,
+		instance: &Instance,
+		settings: &Self::Settings,
+		ticks: i16,
+		pressed: bool,
+	) -> OpenActionResult<()> {
+		let dial_settings = DialSettings::from_settings(settings);
+		if dial_held(&instance.instance_id, pressed, dial_settings.press_source) && dial_settings.cycle {
+			// When pressed, cycle through audio-producing programs (with master volume as first option)
+			let current = dial_target(&instance.instance_id);
 			
-			if !image_set {
-				log::warn!("No icon found for app: {} [{}], using unknown.png", app_name, process_binary);
-				// Use unknown.png as fallback
-				let fallback_path = "icons/unknown.png";
-				log::info!("Setting fallback unknown icon: {}", fallback_path);
-				if let Ok(abs_path) = std::fs::canonicalize(fallback_path) {
-					let file_url = format!("file://{}", abs_path.display());
-					match fetch_and_convert_to_data_url(&file_url).await {
-						Ok(data_url) => {
-							log::info!("Converted to data URL (length: {})", data_url.len());
-							if let Err(e) = instance.set_image(Some(data_url), None).await {
-								log::error!("Failed to set unknown icon: {}", e);
-							} else {
-								log::info!("Successfully set unknown icon");
-							}
-						}
-						Err(e) => {
-							log::error!("Failed to convert {} to data URL: {}", fallback_path, e);
-						}
-					}
-				} else {
-					log::error!("Failed to find {}", fallback_path);
+			match cycle_selection(AUDIO.as_ref(), &current, ticks, &dial_settings.filter).await {
+				Ok(target) => {
+					// Store updated state for this instance, and persist it so it survives restarts
+					remember_settings(&instance.instance_id, settings);
+					save_dial_target(instance, &target).await?;
+					DIAL_STATES.lock().unwrap().insert(instance.instance_id.clone(), target);
+					
+					// Update the image for the selected target
+					update_dial_image_for_selected_sink(instance).await?;
+				}
+				Err(error) => {
+					log::error!("Failed to cycle the dial target: {}", error);
 				}
 			}
+			return Ok(());
 		}
-	} else {
-		log::error!("Failed to get sink input info for ID {}", selected);
+		
+		// Volume control when not pressed - adjust selected source for this instance
+		let target = dial_target(&instance.instance_id);
+		let since_last = LAST_ROTATIONS
+			.lock()
+			.unwrap()
+			.insert(instance.instance_id.clone(), Instant::now())
+			.map(|last| last.elapsed());
+		let mut delta = dial_settings.rotation_delta(ticks, since_last);
+		if dial_settings.curve == VolumeCurve::Cubic {
+			match selected_volume(AUDIO.as_ref(), &target).await {
+				Ok(current) => delta = cubic_delta(current, delta),
+				Err(error) => log::warn!("Failed to read volume of {:?}: {}", target, error),
+			}
+		}
+		match change_selected_volume(AUDIO.as_ref(), &target, delta, dial_settings.limit_for(&target)).await {
+			Ok(volume) => log::info!("Changed volume of {:?} to {}%", target, volume),
+			Err(error) => log::error!("Failed to change volume of {:?}: {}", target, error),
+		}
+		update_dial_feedback(instance).await?;
+		
+		Ok(())
+	}
+
+	async fn dial_down(&self, instance: &Instance, settings: &Self::Settings) -> OpenActionResult<()> {
+		log::info!("Volume dial {} pressed", instance.instance_id);
+		begin_press(instance, DialSettings::from_settings(settings).press_source, |instance| async move {
+			toggle_mute(&instance).await
+		});
+		Ok(())
+	}
+
+	async fn dial_up(&self, instance: &Instance, settings: &Self::Settings) -> OpenActionResult<()> {
+		// A short press without rotating in between toggles mute
+		if end_press(&instance.instance_id, DialSettings::from_settings(settings).press_source) {
+			toggle_mute(instance).await?;
+		}
+		Ok(())
+	}
+
+	async fn touch_tap(
+		&self,
+		instance: &Instance,
+		_: &Self::Settings,
+		_: (u16, u16),
+		_: bool,
+	) -> OpenActionResult<()> {
+		toggle_mute(instance).await
+	}
+}
+
+/// Make the next or previous sink the default, in server order, optionally taking every
+/// playing stream along. Returns the new default sink.
+pub async fn switch_default_sink(audio: &dyn AudioBackend, ticks: i16, move_streams: bool) -> anyhow::Result<Device> {
+	let sinks = audio.sinks().await?;
+	if sinks.is_empty() {
+		anyhow::bail!("No sinks to switch between");
+	}
+	let default_sink = audio.server_info().await?.default_sink;
+	let current_index = sinks
+		.iter()
+		.position(|sink| Some(&sink.name) == default_sink.as_ref())
+		.unwrap_or(0);
+	let sink = sinks[step_index(current_index, sinks.len(), ticks)].clone();
+	audio.set_default_sink(&sink.name).await?;
+
+	if move_streams {
+		for sink_input in audio.sink_inputs().await? {
+			if sink_input.sink != sink.index {
+				audio.move_sink_input(sink_input.index, &sink.name).await?;
+			}
+		}
+	}
+	Ok(sink)
+}
+
+/// Icon for an output device: headphones or a speaker
+fn sink_icon(sink: &Device) -> &'static str {
+	let headphones = ["device.form_factor", "device.icon_name", "device.bus_path"]
+		.into_iter()
+		.filter_map(|key| sink.properties.get(key))
+		.any(|value| value.contains("headphone") || value.contains("headset"));
+	if headphones { "icons/headphones.svg" } else { "icons/speaker.svg" }
+}
+
+/// A bundled or configured icon as a data URL; SVGs are rendered to PNG since dials and keys only
+/// show bitmaps
+async fn icon_data_url(path: &str) -> Option<String> {
+	let abs_path = std::fs::canonicalize(path).ok()?;
+	// Rendering an SVG is CPU bound, keep it off the async runtime
+	tokio::task::spawn_blocking(move || icons::icon_data_url(&abs_path)).await.ok().flatten()
+}
+
+/// Show the current default sink on an output switch
+pub async fn update_output_switch(instance: &Instance) -> OpenActionResult<()> {
+	let sink = match AUDIO.sink(DEFAULT_SINK).await {
+		Ok(sink) => sink,
+		Err(error) => {
+			log::error!("Failed to read the default sink: {}", error);
+			return instance.set_title(Some("No output"), None).await;
+		}
+	};
+	let label = sink.description.clone().unwrap_or_else(|| sink.name.clone());
+	instance.set_title(Some(label), None).await?;
+	if let Some(icon) = icon_data_url(sink_icon(&sink)).await {
+		instance.set_image(Some(icon), None).await?;
+	}
+	Ok(())
+}
+
+async fn switch_output(instance: &Instance, settings: &HashMap<String, String>, ticks: i16) -> OpenActionResult<()> {
+	// Off by default, since it would undo the routing of apps pinned to another output
+	let move_streams = settings.get("moveStreams").is_some_and(|value| value == "true");
+	match switch_default_sink(AUDIO.as_ref(), ticks, move_streams).await {
+		Ok(sink) => log::info!("Switched output to {}", sink.name),
+		Err(error) => log::error!("Failed to switch output: {}", error),
+	}
+	update_output_switch(instance).await
+}
+
+pub struct OutputSwitchAction;
+#[async_trait]
+impl Action for OutputSwitchAction {
+	const UUID: ActionUuid = "PlayMix.outputswitch";
+	type Settings = HashMap<String, String>;
+
+	async fn will_appear(&self, instance: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
+		update_output_switch(instance).await
+	}
+
+	async fn key_down(&self, instance: &Instance, settings: &Self::Settings) -> OpenActionResult<()> {
+		switch_output(instance, settings, 1).await
+	}
+
+	async fn dial_rotate(
+		&self,
+		instance: &Instance,
+		settings: &Self::Settings,
+		ticks: i16,
+		_pressed: bool,
+	) -> OpenActionResult<()> {
+		switch_output(instance, settings, ticks).await
+	}
+}
+
+/// Step a routing dial's selection through the apps that have streams, skipping master volume
+pub async fn cycle_app(
+	audio: &dyn AudioBackend,
+	current: &DialTarget,
+	ticks: i16,
+	filter: &CycleFilter,
+) -> anyhow::Result<Option<DialTarget>> {
+	let sink_inputs = audio.sink_inputs().await?;
+	// Routing always moves whole apps
+	let filter = CycleFilter { split_streams: false, ..filter.clone() };
+	let apps: Vec<DialTarget> = cycle_targets(&sink_inputs, &filter)
+		.into_iter()
+		.filter(|target| *target != DialTarget::Master)
+		.collect();
+	if apps.is_empty() {
+		return Ok(None);
+	}
+	let next = match apps.iter().position(|target| target == current) {
+		Some(index) => step_index(index, apps.len(), ticks),
+		None if ticks > 0 => 0,
+		None => apps.len() - 1,
+	};
+	Ok(Some(apps[next].clone()))
+}
+
+/// The sink an app is playing on, going by its first stream
+pub async fn app_sink(audio: &dyn AudioBackend, app_id: &str) -> anyhow::Result<Option<Device>> {
+	let sink_inputs = audio.sink_inputs().await?;
+	let Some(stream) = app_streams(&sink_inputs, app_id).next() else {
+		return Ok(None);
+	};
+	Ok(audio.sinks().await?.into_iter().find(|sink| sink.index == stream.sink))
+}
+
+/// Move every stream of an app to the next or previous sink, returning that sink
+pub async fn route_app(audio: &dyn AudioBackend, app_id: &str, ticks: i16) -> anyhow::Result<Device> {
+	let sink_inputs = audio.sink_inputs().await?;
+	let streams: Vec<&SinkInput> = app_streams(&sink_inputs, app_id).collect();
+	let Some(first) = streams.first() else {
+		anyhow::bail!("{} has no streams", app_id);
+	};
+	let sinks = audio.sinks().await?;
+	if sinks.is_empty() {
+		anyhow::bail!("No sinks to route to");
+	}
+	let current_index = sinks.iter().position(|sink| sink.index == first.sink).unwrap_or(0);
+	let sink = sinks[step_index(current_index, sinks.len(), ticks)].clone();
+	for stream in streams {
+		audio.move_sink_input(stream.index, &sink.name).await?;
+	}
+	Ok(sink)
+}
+
+/// Show the selected app and the output it plays on on a routing dial
+pub async fn update_route_dial(instance: &Instance) -> OpenActionResult<()> {
+	let DialTarget::App(app_id) = dial_target(&instance.instance_id) else {
+		instance.set_title(Some("Press + rotate\nto pick an app"), None).await?;
+		return instance.set_feedback(&json!({ "title": "No app", "value": "" })).await;
+	};
+	let sink_inputs = AUDIO.sink_inputs().await.unwrap_or_default();
+	let app_name = app_streams(&sink_inputs, &app_id)
+		.next()
+		.map(|sink_input| sink_input.app_name().to_owned())
+		.unwrap_or_else(|| app_id.clone());
+	let sink = app_sink(AUDIO.as_ref(), &app_id).await.unwrap_or_else(|e| {
+		log::warn!("Failed to find the sink of {}: {}", app_id, e);
+		None
+	});
+	let sink_name = match &sink {
+		Some(sink) => sink.description.clone().unwrap_or_else(|| sink.name.clone()),
+		None => "Not playing".to_owned(),
+	};
+	instance.set_title(Some(format!("{}\n{}", app_name, sink_name)), None).await?;
+	instance.set_feedback(&json!({ "title": app_name, "value": sink_name })).await?;
+	if let Some(icon) = icon_data_url(sink.as_ref().map_or("icons/speaker.svg", sink_icon)).await {
+		instance.set_image(Some(icon), None).await?;
+	}
+	Ok(())
+}
+
+pub struct AppRouteAction;
+#[async_trait]
+impl Action for AppRouteAction {
+	const UUID: ActionUuid = "PlayMix.approute";
+	type Settings = HashMap<String, String>;
+
+	async fn will_appear(&self, instance: &Instance, settings: &Self::Settings) -> OpenActionResult<()> {
+		if let Some(target) = DialSettings::from_settings(settings).target {
+			DIAL_STATES.lock().unwrap().insert(instance.instance_id.clone(), target);
+		}
+		update_route_dial(instance).await
+	}
+
+	async fn dial_rotate(
+		&self,
+		instance: &Instance,
+		settings: &Self::Settings,
+		ticks: i16,
+		pressed: bool,
+	) -> OpenActionResult<()> {
+		let dial_settings = DialSettings::from_settings(settings);
+		let current = dial_target(&instance.instance_id);
+
+		// Press + rotate picks the app, rotating moves it to another output
+		if dial_held(&instance.instance_id, pressed, dial_settings.press_source) {
+			match cycle_app(AUDIO.as_ref(), &current, ticks, &dial_settings.filter).await {
+				Ok(Some(target)) => {
+					let mut settings = settings.clone();
+					settings.insert(TARGET_SETTING.to_owned(), target.to_setting());
+					instance.set_settings(&settings).await?;
+					DIAL_STATES.lock().unwrap().insert(instance.instance_id.clone(), target);
+				}
+				Ok(None) => log::info!("No applications are playing"),
+				Err(error) => log::error!("Failed to list audio applications: {}", error),
+			}
+		} else if let DialTarget::App(app_id) = &current {
+			match route_app(AUDIO.as_ref(), app_id, ticks).await {
+				Ok(sink) => log::info!("Routed {} to {}", app_id, sink.name),
+				Err(error) => log::error!("Failed to route {}: {}", app_id, error),
+			}
+		}
+		update_route_dial(instance).await
+	}
+
+	async fn dial_down(&self, instance: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
+		press_dial(&instance.instance_id);
+		Ok(())
+	}
+
+	async fn dial_up(&self, instance: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
+		release_dial(&instance.instance_id);
+		Ok(())
+	}
+}
+
+/// The default source, usually the microphone
+fn default_source() -> DialTarget {
+	DialTarget::Source(DEFAULT_SOURCE.to_owned())
+}
+
+/// Show the default source's level and mute state on a microphone dial
+pub async fn update_mic_dial(instance: &Instance) -> OpenActionResult<()> {
+	let status = selected_status(AUDIO.as_ref(), &default_source()).await.unwrap_or_else(|e| {
+		log::warn!("Failed to read the default source: {}", e);
+		DialStatus { name: "Microphone".to_owned(), volume: None, muted: false }
+	});
+	show_dial_feedback(instance, &status).await?;
+	if let Some(icon) = icon_data_url("icons/mic.svg").await {
+		set_dial_image(instance, icon, status.muted).await?;
 	}
-	
 	Ok(())
 }
 
-pub struct VolumeDialAction;
+/// Refresh a microphone dial's level without touching its image
+pub async fn update_mic_feedback(instance: &Instance) -> OpenActionResult<()> {
+	update_dial_feedback_for(instance, &default_source()).await
+}
+
+/// Show whether the default source is muted on a microphone key
+pub async fn update_mic_mute(instance: &Instance) -> OpenActionResult<()> {
+	match selected_muted(AUDIO.as_ref(), &default_source()).await {
+		Ok(muted) => instance.set_state(muted as u16).await,
+		Err(error) => {
+			log::warn!("Failed to read the default source: {}", error);
+			Ok(())
+		}
+	}
+}
+
+/// Mute or unmute the default source
+async fn set_mic_muted(muted: bool) {
+	match AUDIO.set_source_mute(DEFAULT_SOURCE, muted).await {
+		Ok(()) => log::info!("{} microphone", if muted { "Muted" } else { "Unmuted" }),
+		Err(error) => log::error!("Failed to mute microphone: {}", error),
+	}
+}
+
+async fn toggle_mic_mute() {
+	match toggle_selected_mute(AUDIO.as_ref(), &default_source()).await {
+		Ok(muted) => log::info!("{} microphone", if muted { "Muted" } else { "Unmuted" }),
+		Err(error) => log::error!("Failed to toggle microphone mute: {}", error),
+	}
+}
+
+pub struct MicDialAction;
 #[async_trait]
-impl Action for VolumeDialAction {
-	const UUID: ActionUuid = "PlayMix.volumedialaction";
+impl Action for MicDialAction {
+	const UUID: ActionUuid = "PlayMix.micdial";
 	type Settings = HashMap<String, String>;
 
-	async fn will_appear(&self, _: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
-		update_all().await;
-		Ok(())
+	async fn will_appear(&self, instance: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
+		update_mic_dial(instance).await
 	}
 
 	async fn dial_rotate(
 		&self,
 		instance: &Instance,
-		_: &Self::Settings,
+		settings: &Self::Settings,
 		ticks: i16,
-		_pressed: bool,
+		pressed: bool,
 	) -> OpenActionResult<()> {
-		if ENCODER_PRESSED.load(Ordering::Relaxed) {
-			// When pressed, cycle through audio-producing programs (with master volume as first option)
-			if let Ok(output) = std::process::Command::new("pactl")
-				.args(&["list", "sink-inputs", "short"])
-				.output()
-			{
-				let stdout = String::from_utf8_lossy(&output.stdout);
-				let sink_inputs: Vec<&str> = stdout.lines().filter(|l| !l.is_empty()).collect();
-				
-				// Total items = 1 (master) + number of sink inputs
-				let total_items = sink_inputs.len() + 1;
-				
-				// Get current index for this instance
-				let current_index = {
-					let states = DIAL_STATES.lock().unwrap();
-					states.get(&instance.instance_id).map(|(idx, _)| *idx).unwrap_or(0)
-				};
-				
-				// Calculate new index based on rotation direction
-				let new_index = if ticks > 0 {
-					(current_index + 1) % total_items
-				} else {
-					if current_index == 0 {
-						total_items - 1
-					} else {
-						current_index - 1
-					}
-				};
-				
-				// Update state for this instance
-				let sink_input_id = if new_index == 0 {
-					// Master volume selected
-					log::info!("Switched to: Master Volume (1 of {})", total_items);
-					0
-				} else {
-					// Specific app selected (index - 1 because master is at 0)
-					let sink_index = new_index - 1;
-					if let Some(sink_input_line) = sink_inputs.get(sink_index) {
-						if let Some(sink_input_id_str) = sink_input_line.split_whitespace().next() {
-							if let Ok(sink_input_id) = sink_input_id_str.parse::<usize>() {
-								// Get application name for logging
-								if let Ok(info_output) = std::process::Command::new("pactl")
-									.args(&["list", "sink-inputs"])
-									.output()
-								{
-									let info = String::from_utf8_lossy(&info_output.stdout);
-									let lines = info.lines().skip_while(|line| !line.contains(&format!("Sink Input #{}", sink_input_id)));
-									
-									let app_name = lines.clone()
-										.find(|line| line.contains("application.name"))
-										.and_then(|line| line.split('"').nth(1))
-										.unwrap_or("Unknown");
-									
-									let process_binary = lines.clone()
-										.find(|line| line.contains("application.process.binary"))
-										.and_then(|line| line.split('"').nth(1))
-										.unwrap_or("");
-									
-									log::info!("Switched to audio app: {} [{}] (ID: {}, {} of {})", 
-										app_name, process_binary, sink_input_id, new_index + 1, total_items);
-								}
-								sink_input_id
-							} else {
-								0
-							}
-						} else {
-							0
-						}
-					} else {
-						0
-					}
-				};
-				
-				// Store updated state for this instance
-				DIAL_STATES.lock().unwrap().insert(instance.instance_id.clone(), (new_index, sink_input_id));
-				
-				// Update the image for the selected sink
-				update_dial_image_for_selected_sink(instance).await?;
-			} else {
-				log::error!("Failed to list audio applications");
-			}
-			return Ok(());
-		}
-		
-		// Volume control when not pressed - adjust selected source for this instance
-		let selected = {
-			let states = DIAL_STATES.lock().unwrap();
-			states.get(&instance.instance_id).map(|(_, sink)| *sink).unwrap_or(0)
-		};
-		
-		if selected == 0 {
-			// Master volume
-			let volume_change = if ticks > 0 {
-				format!("{}%+", ticks.abs() * 5)
-			} else {
-				format!("{}%-", ticks.abs() * 5)
-			};
-			
-			if let Err(error) = std::process::Command::new("wpctl")
-				.args(&["set-volume", "@DEFAULT_AUDIO_SINK@", &volume_change, "--limit", "1.0"])
-				.output()
-			{
-				log::error!("Failed to change master volume: {}", error);
-			} else {
-				log::info!("Changed master volume by {}", volume_change);
-			}
-		} else {
-			// Specific app volume - pactl uses +/- prefix format
-			let volume_change = if ticks > 0 {
-				format!("+{}%", ticks.abs() * 5)
-			} else {
-				format!("-{}%", ticks.abs() * 5)
-			};
-			
-			log::info!("Changing app {} volume by {}", selected, volume_change);
-			
-			if let Err(error) = std::process::Command::new("pactl")
-				.args(&["set-sink-input-volume", &selected.to_string(), &volume_change])
-				.output()
-			{
-				log::error!("Failed to change app volume: {}", error);
-			} else {
-				log::info!("Changed app {} volume by {}", selected, volume_change);
-			}
+		let dial_settings = DialSettings::from_settings(settings);
+		// Turning while held still changes the volume, but keeps the press from muting
+		dial_held(&instance.instance_id, pressed, dial_settings.press_source);
+		let target = default_source();
+		let since_last = LAST_ROTATIONS
+			.lock()
+			.unwrap()
+			.insert(instance.instance_id.clone(), Instant::now())
+			.map(|last| last.elapsed());
+		let delta = dial_settings.rotation_delta(ticks, since_last);
+		match change_selected_volume(AUDIO.as_ref(), &target, delta, dial_settings.limit_for(&target)).await {
+			Ok(volume) => log::info!("Changed microphone volume to {}%", volume),
+			Err(error) => log::error!("Failed to change microphone volume: {}", error),
 		}
-		
-		Ok(())
+		update_mic_feedback(instance).await
 	}
 
-	async fn dial_down(&self, _: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
-		ENCODER_PRESSED.store(true, Ordering::Relaxed);
-		log::info!("Volume dial pressed");
+	async fn dial_down(&self, instance: &Instance, settings: &Self::Settings) -> OpenActionResult<()> {
+		begin_press(instance, DialSettings::from_settings(settings).press_source, |instance| async move {
+			toggle_mic_mute().await;
+			update_mic_dial(&instance).await
+		});
 		Ok(())
 	}
 
-	async fn dial_up(&self, _: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
-		ENCODER_PRESSED.store(false, Ordering::Relaxed);
-		log::info!("Volume dial released");
+	async fn dial_up(&self, instance: &Instance, settings: &Self::Settings) -> OpenActionResult<()> {
+		// A short press without rotating in between toggles mute
+		if end_press(&instance.instance_id, DialSettings::from_settings(settings).press_source) {
+			toggle_mic_mute().await;
+			update_mic_dial(instance).await?;
+		}
 		Ok(())
 	}
+
+	async fn touch_tap(
+		&self,
+		instance: &Instance,
+		_: &Self::Settings,
+		_: (u16, u16),
+		_: bool,
+	) -> OpenActionResult<()> {
+		toggle_mic_mute().await;
+		update_mic_dial(instance).await
+	}
+}
+
+pub struct MicMuteAction;
+#[async_trait]
+impl Action for MicMuteAction {
+	const UUID: ActionUuid = "PlayMix.micmute";
+	type Settings = HashMap<String, String>;
+
+	async fn will_appear(&self, instance: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
+		update_mic_mute(instance).await
+	}
+
+	async fn key_down(&self, instance: &Instance, settings: &Self::Settings) -> OpenActionResult<()> {
+		match settings.get("mode").map(String::as_str) {
+			Some("push-to-mute") => set_mic_muted(true).await,
+			_ => toggle_mic_mute().await,
+		}
+		update_mic_mute(instance).await
+	}
+
+	async fn key_up(&self, instance: &Instance, settings: &Self::Settings) -> OpenActionResult<()> {
+		match settings.get("mode").map(String::as_str) {
+			Some("push-to-mute") => set_mic_muted(false).await,
+			_ => return Ok(()),
+		}
+		update_mic_mute(instance).await
+	}
 }
 
 pub struct DialTestAction;
@@ -335,38 +1440,132 @@ impl Action for DialTestAction {
 		instance: &Instance,
 		_: &Self::Settings,
 		ticks: i16,
-		_pressed: bool,
+		pressed: bool,
 	) -> OpenActionResult<()> {
 		log::info!("Dial rotated on instance {}: ticks = {}", instance.instance_id, ticks);
-		log::info!("Dial pressed state: {}", ENCODER_PRESSED.load(Ordering::Relaxed));
+		log::info!("Dial pressed state: events {}, flag {}", rotate_pressed_dial(&instance.instance_id), pressed);
 		Ok(())
 
 	}
 
 	async fn dial_down(&self, instance: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
-		ENCODER_PRESSED.store(true, Ordering::Relaxed);
+		press_dial(&instance.instance_id);
 		log::info!("Dial button pressed on instance {}", instance.instance_id);
 		Ok(())
 	}
 
 	async fn dial_up(&self, instance: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
-		ENCODER_PRESSED.store(false, Ordering::Relaxed);
-		log::info!("Dial button released on instance {}", instance.instance_id);
+		let press = release_dial(&instance.instance_id);
+		log::info!("Dial button released on instance {}: {:?}", instance.instance_id, press);
 		Ok(())
 	}
 }
 
+/// Title and artist of the active player's track
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Track {
+	pub title: String,
+	pub artist: String,
+}
+
+/// Characters of a line that fit on a key
+const TITLE_WIDTH: usize = 9;
+
+/// Space between the end of a scrolling line and its start coming around again
+const SCROLL_GAP: usize = 3;
+
+/// How often long titles move on by one character
+pub const SCROLL_INTERVAL: Duration = Duration::from_millis(400);
+
+/// `width` characters of `text` starting `offset` characters in, wrapping around. Text that fits
+/// is returned as it is.
+pub fn scroll_text(text: &str, width: usize, offset: usize) -> String {
+	let chars: Vec<char> = text.chars().collect();
+	if chars.len() <= width {
+		return text.to_owned();
+	}
+	let length = chars.len() + SCROLL_GAP;
+	(0..width)
+		.map(|i| chars.get((offset + i) % length).copied().unwrap_or(' '))
+		.collect()
+}
+
+impl Track {
+	/// Whether either line is too long for the key and has to scroll
+	pub fn scrolls(&self) -> bool {
+		[&self.title, &self.artist].iter().any(|line| line.chars().count() > TITLE_WIDTH)
+	}
+
+	/// Key title with the title and artist on their own lines, scrolled by `offset`
+	pub fn key_title(&self, offset: usize) -> String {
+		[&self.title, &self.artist]
+			.iter()
+			.filter(|line| !line.is_empty())
+			.map(|line| scroll_text(line, TITLE_WIDTH, offset))
+			.collect::<Vec<_>>()
+			.join("\n")
+	}
+}
+
+/// Show the track on every Now Playing key and every Play/Pause key with its title turned on
+pub async fn update_track_titles() {
+	let title = NOW_PLAYING
+		.lock()
+		.unwrap()
+		.as_ref()
+		.map(|track| track.key_title(SCROLL_OFFSET.load(Ordering::Relaxed)))
+		.unwrap_or_default();
+	let play_pause_titles = PLAY_PAUSE_TITLES.lock().unwrap().clone();
+	let instances = visible_instances(NowPlayingAction::UUID).await.into_iter().chain(
+		visible_instances(PlayPauseAction::UUID)
+			.await
+			.into_iter()
+			.filter(|instance| play_pause_titles.contains(&instance.instance_id)),
+	);
+	for instance in instances {
+		if let Err(e) = instance.set_title(Some(title.clone()), None).await {
+			log::error!("Failed to set track title: {}", e);
+		}
+	}
+}
+
+/// Remember whether a Play/Pause key shows the track, clearing its title when turned off
+async fn apply_play_pause_title(instance: &Instance, settings: &HashMap<String, String>) -> OpenActionResult<()> {
+	let show = settings.get("showTitle").is_some_and(|value| value == "true");
+	let changed = if show {
+		PLAY_PAUSE_TITLES.lock().unwrap().insert(instance.instance_id.clone())
+	} else {
+		PLAY_PAUSE_TITLES.lock().unwrap().remove(&instance.instance_id)
+	};
+	if changed && !show {
+		instance.set_title(Some(""), None).await?;
+	}
+	Ok(())
+}
+
 pub struct PlayPauseAction;
 #[async_trait]
 impl Action for PlayPauseAction {
 	const UUID: ActionUuid = "PlayMix.playpause";
 	type Settings = HashMap<String, String>;
 
-	async fn will_appear(&self, _: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
+	async fn will_appear(&self, instance: &Instance, settings: &Self::Settings) -> OpenActionResult<()> {
+		apply_play_pause_title(instance, settings).await?;
 		update_all().await;
 		Ok(())
 	}
 
+	async fn will_disappear(&self, instance: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
+		PLAY_PAUSE_TITLES.lock().unwrap().remove(&instance.instance_id);
+		Ok(())
+	}
+
+	async fn did_receive_settings(&self, instance: &Instance, settings: &Self::Settings) -> OpenActionResult<()> {
+		apply_play_pause_title(instance, settings).await?;
+		update_track_titles().await;
+		Ok(())
+	}
+
 	async fn key_up(&self, _: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
 		log::info!("PlayPause key_up triggered");
 		if let Err(error) = call_mpris_method("PlayPause").await {
@@ -379,6 +1578,123 @@ impl Action for PlayPauseAction {
 	}
 }
 
+pub struct NowPlayingAction;
+#[async_trait]
+impl Action for NowPlayingAction {
+	const UUID: ActionUuid = "PlayMix.nowplaying";
+	type Settings = HashMap<String, String>;
+
+	async fn will_appear(&self, _: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
+		update_all().await;
+		Ok(())
+	}
+}
+
+/// Where the active player is in its track, in microseconds
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrackProgress {
+	pub title: String,
+	pub position: Option<i64>,
+	pub length: Option<i64>,
+}
+
+/// `m:ss`, or `h:mm:ss` for an hour or more
+pub fn format_time(microseconds: i64) -> String {
+	let seconds = microseconds.max(0) / 1_000_000;
+	let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+	if hours > 0 {
+		format!("{}:{:02}:{:02}", hours, minutes, seconds)
+	} else {
+		format!("{}:{:02}", minutes, seconds)
+	}
+}
+
+impl TrackProgress {
+	/// Where a playing track is expected to be `elapsed` after this reading, without asking the player
+	pub fn advanced(&self, elapsed: Duration) -> TrackProgress {
+		let position = self.position.map(|position| {
+			let position = position.saturating_add(elapsed.as_micros() as i64);
+			self.length.filter(|&length| length > 0).map_or(position, |length| position.min(length))
+		});
+		TrackProgress { position, ..self.clone() }
+	}
+
+	/// Payload for the `$B1` feedback layout: title, position and length, and a progress bar
+	pub fn feedback(&self) -> serde_json::Value {
+		let value = match (self.position, self.length) {
+			(Some(position), Some(length)) => format!("{} / {}", format_time(position), format_time(length)),
+			(Some(position), None) => format_time(position),
+			(None, _) => "-".to_owned(),
+		};
+		let percent = match (self.position, self.length) {
+			(Some(position), Some(length)) if length > 0 => (position.clamp(0, length) * 100 / length) as u32,
+			_ => 0,
+		};
+		json!({
+			"title": if self.title.is_empty() { "Seek" } else { self.title.as_str() },
+			"value": value,
+			"indicator": { "value": percent },
+		})
+	}
+}
+
+/// Show the active player's position on a seek dial
+pub async fn update_seek_dial(instance: &Instance) -> OpenActionResult<()> {
+	let progress = get_track_progress().await.unwrap_or_default();
+	instance.set_feedback(&progress.feedback()).await
+}
+
+/// Play or pause from a seek dial, then refresh everything showing the playback state
+async fn seek_dial_play_pause(instance: &Instance) -> OpenActionResult<()> {
+	if let Err(error) = call_mpris_method("PlayPause").await {
+		log::error!("Failed to make PlayPause MPRIS call: {}", error);
+	}
+	tokio::time::sleep(Duration::from_millis(100)).await;
+	update_all().await;
+	update_seek_dial(instance).await
+}
+
+/// Seconds a seek dial moves per tick, at most ten minutes as in the property inspector
+pub fn seek_step(settings: &HashMap<String, String>) -> i64 {
+	settings
+		.get("step")
+		.and_then(|step| step.trim().parse::<i64>().ok())
+		.filter(|&step| step > 0)
+		.map_or(5, |step| step.min(600))
+}
+
+pub struct SeekDialAction;
+#[async_trait]
+impl Action for SeekDialAction {
+	const UUID: ActionUuid = "PlayMix.seekdial";
+	type Settings = HashMap<String, String>;
+
+	async fn will_appear(&self, instance: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
+		update_seek_dial(instance).await
+	}
+
+	async fn dial_rotate(
+		&self,
+		instance: &Instance,
+		settings: &Self::Settings,
+		ticks: i16,
+		_: bool,
+	) -> OpenActionResult<()> {
+		if let Err(error) = seek_active_player(ticks as i64 * seek_step(settings) * 1_000_000).await {
+			log::error!("Failed to seek: {}", error);
+		}
+		update_seek_dial(instance).await
+	}
+
+	async fn dial_down(&self, instance: &Instance, _: &Self::Settings) -> OpenActionResult<()> {
+		seek_dial_play_pause(instance).await
+	}
+
+	async fn touch_tap(&self, instance: &Instance, _: &Self::Settings, _: (u16, u16), _: bool) -> OpenActionResult<()> {
+		seek_dial_play_pause(instance).await
+	}
+}
+
 pub struct StopAction;
 #[async_trait]
 impl Action for StopAction {
@@ -440,4 +1756,416 @@ impl Action for NextAction {
 		update_all().await;
 		Ok(())
 	}
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::audio::{MockBackend, VOLUME_NORM};
+
+	fn mock_with_apps(apps: &[(u32, &str)]) -> MockBackend {
+		let audio = MockBackend::default();
+		audio.state().sink_inputs = apps
+			.iter()
+			.map(|&(index, binary)| SinkInput {
+				index,
+				volume: vec![VOLUME_NORM / 2; 2],
+				properties: HashMap::from([("application.process.binary".to_owned(), binary.to_owned())]),
+				..Default::default()
+			})
+			.collect();
+		audio
+	}
+
+	fn app(app_id: &str) -> DialTarget {
+		DialTarget::App(app_id.to_owned())
+	}
+
+	#[test]
+	fn dial_targets_round_trip_through_settings() {
+		for target in [DialTarget::Master, app("firefox"), app("org.mozilla.firefox")] {
+			assert_eq!(DialTarget::from_setting(&target.to_setting()), Some(target));
+		}
+		assert_eq!(DialTarget::from_setting("app:"), None);
+		assert_eq!(DialTarget::from_setting("bogus"), None);
+	}
+
+	#[test]
+	fn pinned_devices_round_trip_through_settings() {
+		let sink = DialTarget::Sink("alsa_output.usb-Focusrite.analog-stereo".to_owned());
+		let source = DialTarget::Source("alsa_input.pci-0000_00_1f.3:capture".to_owned());
+		for target in [sink, source] {
+			assert_eq!(DialTarget::from_setting(&target.to_setting()), Some(target));
+		}
+	}
+
+	#[test]
+	fn dial_settings_fall_back_to_defaults() {
+		let defaults = DialSettings::from_settings(&HashMap::new());
+		assert_eq!(
+			defaults,
+			DialSettings {
+				target: None,
+				step: 5,
+				limit: None,
+				cycle: true,
+				acceleration: false,
+				curve: VolumeCurve::Linear,
+				press_source: PressSource::Auto,
+				filter: CycleFilter { skip_system_sounds: true, ..Default::default() },
+			}
+		);
+		assert_eq!(defaults.limit_for(&DialTarget::Master), Some(100));
+		assert_eq!(defaults.limit_for(&app("firefox")), None);
+
+		let settings = HashMap::from([
+			("target".to_owned(), "app:spotify".to_owned()),
+			("step".to_owned(), "2".to_owned()),
+			("limit".to_owned(), "150".to_owned()),
+			("cycle".to_owned(), "false".to_owned()),
+		]);
+		let configured = DialSettings::from_settings(&settings);
+		assert_eq!(configured.target, Some(app("spotify")));
+		assert_eq!(configured.step, 2);
+		assert_eq!(configured.limit_for(&DialTarget::Master), Some(150));
+		assert!(!configured.cycle);
+
+		let malformed = HashMap::from([("step".to_owned(), "0".to_owned()), ("limit".to_owned(), "lots".to_owned())]);
+		assert_eq!(DialSettings::from_settings(&malformed).step, 5);
+		assert_eq!(DialSettings::from_settings(&malformed).limit, None);
+	}
+
+	#[test]
+	fn acceleration_scales_steps_with_rotation_speed() {
+		let plain = DialSettings::from_settings(&HashMap::new());
+		assert_eq!(plain.rotation_delta(1, Some(Duration::from_millis(10))), 5);
+		assert_eq!(plain.rotation_delta(-2, None), -10);
+
+		let accelerated = DialSettings { acceleration: true, ..plain };
+		assert_eq!(accelerated.rotation_delta(1, None), 1);
+		assert_eq!(accelerated.rotation_delta(-1, Some(Duration::from_secs(1))), -1);
+		assert_eq!(accelerated.rotation_delta(1, Some(Duration::from_millis(150))), 5);
+		assert_eq!(accelerated.rotation_delta(2, Some(Duration::from_millis(40))), 30);
+		assert_eq!(accelerated.rotation_delta(3, None), 15);
+	}
+
+	#[test]
+	fn cubic_curve_takes_finer_steps_near_silence() {
+		assert!(cubic_delta(5, 5).abs() < cubic_delta(80, 5).abs());
+		assert_eq!(cubic_delta(0, 1), 1);
+		assert_eq!(cubic_delta(0, -5), -1);
+		assert_eq!(cubic_delta(100, -100), -100);
+		// One step up and down lands back where it started
+		let up = cubic_delta(40, 5);
+		assert_eq!(40 + up + cubic_delta((40 + up) as u32, -5), 40);
+	}
+
+	#[tokio::test]
+	async fn muting_an_app_mutes_all_its_streams() {
+		let audio = mock_with_apps(&[(12, "firefox"), (13, "firefox"), (40, "discord")]);
+		audio.state().sink_inputs[1].muted = true;
+
+		assert!(!selected_muted(&audio, &app("firefox")).await.unwrap());
+		assert!(toggle_selected_mute(&audio, &app("firefox")).await.unwrap());
+		assert!(audio.state().sink_inputs[0].muted);
+		assert!(!audio.state().sink_inputs[2].muted);
+
+		assert!(!toggle_selected_mute(&audio, &app("firefox")).await.unwrap());
+		assert!(!audio.state().sink_inputs[1].muted);
+
+		// An app between streams has nothing to mute
+		assert!(toggle_selected_mute(&audio, &app("spotify")).await.is_err());
+	}
+
+	#[tokio::test]
+	async fn muting_master_mutes_the_default_sink() {
+		let audio = mock_with_apps(&[]);
+
+		assert!(toggle_selected_mute(&audio, &DialTarget::Master).await.unwrap());
+		assert!(audio.state().sinks[0].muted);
+	}
+
+	#[tokio::test]
+	async fn status_shows_the_loudest_stream_of_an_app() {
+		let audio = mock_with_apps(&[(12, "firefox"), (13, "firefox")]);
+		audio.state().sink_inputs[1].volume = vec![VOLUME_NORM; 2];
+
+		let status = selected_status(&audio, &app("firefox")).await.unwrap();
+		assert_eq!(status, DialStatus { name: "Unknown".to_owned(), volume: Some(100), muted: false });
+		assert_eq!(status.title(), "Unknown\n100%");
+		assert_eq!(status.feedback()["indicator"]["value"], 100);
+
+		let gone = selected_status(&audio, &app("spotify")).await.unwrap();
+		assert_eq!(gone.title(), "spotify\n-");
+	}
+
+	#[tokio::test]
+	async fn status_of_a_muted_device_says_so() {
+		let audio = mock_with_apps(&[]);
+		audio.state().sinks[0].muted = true;
+
+		let master = selected_status(&audio, &DialTarget::Master).await.unwrap();
+		assert_eq!(master.title(), "Master\nMuted");
+		assert_eq!(master.feedback()["indicator"]["value"], 50);
+
+		let sink = selected_status(&audio, &DialTarget::Sink("mock_sink".to_owned())).await.unwrap();
+		assert_eq!(sink.name, "Mock Output");
+	}
+
+	#[tokio::test]
+	async fn app_volume_respects_a_configured_limit() {
+		let audio = mock_with_apps(&[(12, "firefox")]);
+
+		assert_eq!(change_selected_volume(&audio, &app("firefox"), 80, Some(110)).await.unwrap(), 110);
+	}
+
+	#[test]
+	fn each_dial_has_its_own_press_state() {
+		let mut presses = DialPresses::default();
+		presses.press("dial-a");
+		assert!(presses.rotate_pressed("dial-a"));
+		assert!(!presses.rotate_pressed("dial-b"));
+
+		presses.press("dial-b");
+		let a = presses.release("dial-a").unwrap();
+		let b = presses.release("dial-b").unwrap();
+		assert!(!a.is_tap());
+		assert!(b.is_tap());
+		assert!(!presses.rotate_pressed("dial-a"));
+	}
+
+	#[test]
+	fn press_sources_decide_whether_a_dial_is_held() {
+		let mut presses = DialPresses::default();
+		assert!(presses.held("flag-dial", true, PressSource::Flag));
+		assert!(!presses.held("flag-dial", false, PressSource::Flag));
+
+		// With the pressed flag, only a rotation while held cancels the press's mute
+		let pressed_at = presses.press("flag-dial");
+		assert!(!presses.held("flag-dial", false, PressSource::Flag));
+		assert!(presses.settle("flag-dial", pressed_at));
+		let pressed_at = presses.press("flag-dial");
+		assert!(presses.held("flag-dial", true, PressSource::Flag));
+		assert!(!presses.settle("flag-dial", pressed_at));
+		// A newer press is settled by its own pending mute
+		let stale = presses.press("flag-dial");
+		let pressed_at = presses.press("flag-dial");
+		assert!(!presses.settle("flag-dial", stale));
+		assert!(presses.settle("flag-dial", pressed_at));
+
+		presses.press("events-dial");
+		assert!(presses.held("events-dial", false, PressSource::Events));
+		presses.release("events-dial");
+		assert!(!presses.held("events-dial", true, PressSource::Events));
+
+		// A press that never gets released is only trusted once releases are known to arrive
+		presses.press("stuck-dial");
+		assert!(!presses.held("stuck-dial", false, PressSource::Auto));
+		assert!(presses.held("stuck-dial", true, PressSource::Auto));
+		presses.saw_release();
+		assert!(presses.held("stuck-dial", false, PressSource::Auto));
+		presses.release("stuck-dial");
+		assert!(!presses.held("stuck-dial", false, PressSource::Auto));
+	}
+
+	#[tokio::test]
+	async fn routing_moves_every_stream_of_one_app() {
+		let audio = mock_with_apps(&[(12, "firefox"), (40, "discord"), (41, "discord")]);
+		audio.state().sinks.push(Device { index: 1, name: "headset".to_owned(), ..Default::default() });
+
+		assert_eq!(cycle_app(&audio, &DialTarget::Master, 1, &CycleFilter::default()).await.unwrap(), Some(app("firefox")));
+		assert_eq!(cycle_app(&audio, &app("firefox"), 1, &CycleFilter::default()).await.unwrap(), Some(app("discord")));
+		assert_eq!(cycle_app(&audio, &app("discord"), 1, &CycleFilter::default()).await.unwrap(), Some(app("firefox")));
+
+		assert_eq!(route_app(&audio, "discord", 1).await.unwrap().name, "headset");
+		let sinks: Vec<u32> = audio.state().sink_inputs.iter().map(|sink_input| sink_input.sink).collect();
+		assert_eq!(sinks, vec![0, 1, 1]);
+		assert_eq!(app_sink(&audio, "discord").await.unwrap().unwrap().name, "headset");
+
+		assert!(route_app(&audio, "spotify", 1).await.is_err());
+	}
+
+	#[tokio::test]
+	async fn microphone_follows_the_default_source() {
+		let audio = mock_with_apps(&[]);
+		{
+			let mut state = audio.state();
+			state.sources.push(Device {
+				index: 3,
+				name: "usb_mic".to_owned(),
+				description: Some("USB Microphone".to_owned()),
+				volume: vec![VOLUME_NORM],
+				..Default::default()
+			});
+			state.server.default_source = Some("usb_mic".to_owned());
+		}
+
+		assert!(toggle_selected_mute(&audio, &default_source()).await.unwrap());
+		assert!(audio.state().sources[0].muted);
+		change_selected_volume(&audio, &default_source(), 10, Some(100)).await.unwrap();
+		let status = selected_status(&audio, &default_source()).await.unwrap();
+		assert_eq!(status, DialStatus { name: "USB Microphone".to_owned(), volume: Some(100), muted: true });
+	}
+
+	#[tokio::test]
+	async fn switching_output_wraps_around_and_moves_streams() {
+		let audio = mock_with_apps(&[(12, "firefox"), (40, "discord")]);
+		audio.state().sinks.push(Device { index: 1, name: "headphones".to_owned(), ..Default::default() });
+
+		let sink = switch_default_sink(&audio, 1, true).await.unwrap();
+		assert_eq!(sink.name, "headphones");
+		assert_eq!(audio.state().server.default_sink.as_deref(), Some("headphones"));
+		assert!(audio.state().sink_inputs.iter().all(|sink_input| sink_input.sink == 1));
+
+		let sink = switch_default_sink(&audio, 1, false).await.unwrap();
+		assert_eq!(sink.name, "mock_sink");
+		assert!(audio.state().sink_inputs.iter().all(|sink_input| sink_input.sink == 1));
+
+		assert_eq!(switch_default_sink(&audio, -1, false).await.unwrap().name, "headphones");
+	}
+
+	#[tokio::test]
+	async fn cycling_visits_master_and_each_app_once() {
+		let audio = mock_with_apps(&[(12, "firefox"), (13, "firefox"), (40, "discord")]);
+
+		assert_eq!(cycle_selection(&audio, &DialTarget::Master, 1, &CycleFilter::default()).await.unwrap(), app("firefox"));
+		assert_eq!(cycle_selection(&audio, &app("firefox"), 1, &CycleFilter::default()).await.unwrap(), app("discord"));
+		assert_eq!(cycle_selection(&audio, &app("discord"), 1, &CycleFilter::default()).await.unwrap(), DialTarget::Master);
+		assert_eq!(cycle_selection(&audio, &DialTarget::Master, -1, &CycleFilter::default()).await.unwrap(), app("discord"));
+	}
+
+	#[test]
+	fn cycle_list_can_be_filtered_and_pinned() {
+		let mut sink_inputs: Vec<SinkInput> = ["firefox", "discord", "canberra-gtk-play", "spotify", "vlc"]
+			.into_iter()
+			.enumerate()
+			.map(|(index, binary)| SinkInput {
+				index: index as u32,
+				properties: HashMap::from([("application.process.binary".to_owned(), binary.to_owned())]),
+				..Default::default()
+			})
+			.collect();
+		sink_inputs[2].properties.insert("media.role".to_owned(), "event".to_owned());
+		sink_inputs[4].corked = true;
+
+		let settings = HashMap::from([
+			("exclude".to_owned(), "Firefox".to_owned()),
+			("order".to_owned(), "master, Spotify ,discord, nonexistent".to_owned()),
+			("skipIdle".to_owned(), "true".to_owned()),
+		]);
+		let filter = DialSettings::from_settings(&settings).filter;
+		assert_eq!(cycle_targets(&sink_inputs, &filter), vec![DialTarget::Master, app("spotify"), app("discord")]);
+
+		let pinned_after_master = CycleFilter { order: vec!["vlc".to_owned()], ..Default::default() };
+		assert_eq!(
+			cycle_targets(&sink_inputs, &pinned_after_master),
+			vec![DialTarget::Master, app("vlc"), app("firefox"), app("discord"), app("canberra-gtk-play"), app("spotify")]
+		);
+
+		let only = CycleFilter { include: vec!["vlc".to_owned()], order: vec!["vlc".to_owned(), "master".to_owned()], ..Default::default() };
+		assert_eq!(cycle_targets(&sink_inputs, &only), vec![app("vlc"), DialTarget::Master]);
+
+		// A Flatpak app is keyed by its app ID but still listed by process
+		sink_inputs[3].properties.insert("pipewire.access.portal.app_id".to_owned(), "com.spotify.Client".to_owned());
+		let no_spotify = CycleFilter { exclude: vec!["spotify".to_owned()], ..Default::default() };
+		assert!(!cycle_targets(&sink_inputs, &no_spotify).contains(&app("com.spotify.client")));
+	}
+
+	#[tokio::test]
+	async fn ungrouped_dials_visit_and_control_single_streams() {
+		let audio = mock_with_apps(&[(12, "chrome"), (13, "chrome"), (40, "discord")]);
+		audio.state().sink_inputs[1].name = Some("Radio".to_owned());
+		let settings = HashMap::from([("group".to_owned(), "false".to_owned()), ("order".to_owned(), "discord".to_owned())]);
+		let filter = DialSettings::from_settings(&settings).filter;
+
+		let targets = cycle_targets(&audio.state().sink_inputs, &filter);
+		assert_eq!(targets, vec![DialTarget::Master, DialTarget::Stream(40), DialTarget::Stream(12), DialTarget::Stream(13)]);
+		// A stream is saved as its app, since its index is gone after a restart
+		assert_eq!(DialTarget::Stream(13).persisted(&audio.state().sink_inputs), app("chrome"));
+		assert_eq!(DialTarget::Stream(99).persisted(&audio.state().sink_inputs), DialTarget::Master);
+		assert_eq!(DialTarget::from_setting("stream:13"), None);
+
+		assert_eq!(change_selected_volume(&audio, &DialTarget::Stream(13), 10, None).await.unwrap(), 60);
+		assert_eq!(volume_percent(&audio.state().sink_inputs[0].volume), 50);
+		let status = selected_status(&audio, &DialTarget::Stream(13)).await.unwrap();
+		assert_eq!(status.name, "Unknown: Radio");
+	}
+
+	#[tokio::test]
+	async fn cycling_from_an_app_that_went_away_starts_over() {
+		let audio = mock_with_apps(&[(12, "firefox")]);
+
+		assert_eq!(cycle_selection(&audio, &app("spotify"), 1, &CycleFilter::default()).await.unwrap(), app("firefox"));
+	}
+
+	#[tokio::test]
+	async fn master_volume_is_capped_at_100_percent() {
+		let audio = mock_with_apps(&[]);
+
+		let settings = DialSettings::from_settings(&HashMap::new());
+		let limit = settings.limit_for(&DialTarget::Master);
+		for _ in 0..20 {
+			change_selected_volume(&audio, &DialTarget::Master, settings.step as i32, limit).await.unwrap();
+		}
+		assert_eq!(volume_percent(&audio.state().sinks[0].volume), 100);
+	}
+
+	#[tokio::test]
+	async fn app_volume_changes_every_stream_of_that_app() {
+		let audio = mock_with_apps(&[(12, "firefox"), (40, "discord"), (41, "discord")]);
+
+		assert_eq!(change_selected_volume(&audio, &app("discord"), -10, None).await.unwrap(), 40);
+		let state = audio.state();
+		assert_eq!(volume_percent(&state.sink_inputs[0].volume), 50);
+		assert_eq!(volume_percent(&state.sink_inputs[1].volume), 40);
+		assert_eq!(volume_percent(&state.sink_inputs[2].volume), 40);
+		assert_eq!(volume_percent(&state.sinks[0].volume), 50);
+	}
+
+	#[test]
+	fn long_titles_scroll_and_wrap_around() {
+		assert_eq!(scroll_text("Intro", 9, 4), "Intro");
+		assert_eq!(scroll_text("Bohemian Rhapsody", 9, 0), "Bohemian ");
+		assert_eq!(scroll_text("Bohemian Rhapsody", 9, 9), "Rhapsody ");
+		assert_eq!(scroll_text("Bohemian Rhapsody", 9, 15), "dy   Bohe");
+		assert_eq!(scroll_text("Bohemian Rhapsody", 9, 20), "Bohemian ");
+
+		let track = Track { title: "Bohemian Rhapsody".to_owned(), artist: "Queen".to_owned() };
+		assert!(track.scrolls());
+		assert_eq!(track.key_title(9), "Rhapsody \nQueen");
+		assert_eq!(Track { title: "Intro".to_owned(), artist: String::new() }.key_title(3), "Intro");
+	}
+
+	#[test]
+	fn seek_feedback_shows_position_and_length() {
+		assert_eq!(format_time(83_500_000), "1:23");
+		assert_eq!(format_time(3_723_000_000), "1:02:03");
+		assert_eq!(format_time(-5), "0:00");
+
+		let progress = TrackProgress { title: "Intro".to_owned(), position: Some(60_000_000), length: Some(240_000_000) };
+		assert_eq!(
+			progress.feedback(),
+			json!({ "title": "Intro", "value": "1:00 / 4:00", "indicator": { "value": 25 } })
+		);
+		assert_eq!(TrackProgress::default().feedback()["value"], "-");
+		assert_eq!(TrackProgress::default().feedback()["title"], "Seek");
+	}
+
+	#[test]
+	fn seek_steps_stay_within_the_inspector_range() {
+		let step = |value: &str| seek_step(&HashMap::from([("step".to_owned(), value.to_owned())]));
+		assert_eq!(seek_step(&HashMap::new()), 5);
+		assert_eq!(step("30"), 30);
+		assert_eq!(step("0"), 5);
+		assert_eq!(step("9223372036854775807"), 600);
+	}
+
+	#[test]
+	fn playing_tracks_advance_up_to_their_length() {
+		let progress = TrackProgress { title: "Intro".to_owned(), position: Some(60_000_000), length: Some(61_000_000) };
+		assert_eq!(progress.advanced(Duration::from_millis(500)).position, Some(60_500_000));
+		assert_eq!(progress.advanced(Duration::from_secs(5)).position, Some(61_000_000));
+
+		let unknown = TrackProgress { position: None, ..progress };
+		assert_eq!(unknown.advanced(Duration::from_secs(5)).position, None);
+	}
+}
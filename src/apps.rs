@@ -0,0 +1,128 @@
+//! User-editable table telling which applications have an MPRIS player and which icon they use.
+//!
+//! The table is read from `apps.json` in the plugin directory. Keys are matched against a stream's
+//! process binary, application ID and application name, ignoring case, and failing that looked for
+//! within them.
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+pub const APPS_FILE: &str = "apps.json";
+
+/// What the plugin knows about one application
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppConfig {
+	/// Whether the app exposes an MPRIS player that may have album art
+	#[serde(default)]
+	pub mpris: bool,
+	/// The part of the bus name after `org.mpris.MediaPlayer2.`, if it differs from the binary
+	pub bus_name: Option<String>,
+	/// Icon file, relative to `icons/` or absolute
+	pub icon: Option<String>,
+}
+
+impl AppConfig {
+	/// Path of the configured icon as seen from the plugin directory
+	pub fn icon_path(&self) -> Option<String> {
+		self.icon.as_ref().map(|icon| {
+			if icon.starts_with('/') { icon.clone() } else { format!("icons/{}", icon) }
+		})
+	}
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AppTable(HashMap<String, AppConfig>);
+
+impl AppTable {
+	pub fn parse(json: &str) -> Result<Self> {
+		let entries: HashMap<String, AppConfig> = serde_json::from_str(json)?;
+		Ok(AppTable(entries.into_iter().map(|(name, config)| (name.to_lowercase(), config)).collect()))
+	}
+
+	/// Read the table, falling back to an empty one so every app just gets its icon looked up by name
+	pub fn load(path: &str) -> Self {
+		let json = match std::fs::read_to_string(path) {
+			Ok(json) => json,
+			Err(e) => {
+				log::warn!("Failed to read {}: {}", path, e);
+				return AppTable::default();
+			}
+		};
+		match AppTable::parse(&json) {
+			Ok(table) => {
+				log::info!("Loaded {} application entries from {}", table.0.len(), path);
+				table
+			}
+			Err(e) => {
+				log::error!("Failed to parse {}: {}", path, e);
+				AppTable::default()
+			}
+		}
+	}
+
+	/// The entry for the first of `names` that has one. Failing that, the longest key found within
+	/// one of the names, so `firefox-esr` and "Google Chrome" use the `firefox` and `chrome`
+	/// entries; the key then also stands in for the bus name.
+	pub fn lookup(&self, names: &[&str]) -> Option<AppConfig> {
+		let names: Vec<String> = names.iter().filter(|name| !name.is_empty()).map(|name| name.to_lowercase()).collect();
+		if let Some(config) = names.iter().find_map(|name| self.0.get(name)) {
+			return Some(config.clone());
+		}
+		names.iter().find_map(|name| {
+			let (key, config) = self
+				.0
+				.iter()
+				.filter(|(key, _)| !key.is_empty() && name.contains(key.as_str()))
+				.max_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| b.cmp(a)))?;
+			Some(AppConfig { bus_name: config.bus_name.clone().or_else(|| Some(key.clone())), ..config.clone() })
+		})
+	}
+}
+
+pub static APPS: Lazy<AppTable> = Lazy::new(|| AppTable::load(APPS_FILE));
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn bundled_table_parses() {
+		let table = AppTable::parse(include_str!("../assets/apps.json")).unwrap();
+
+		let chrome = table.lookup(&["chrome"]).unwrap();
+		assert!(chrome.mpris);
+		assert_eq!(chrome.bus_name.as_deref(), Some("chromium"));
+		assert_eq!(chrome.icon_path().as_deref(), Some("icons/chrome.png"));
+		assert!(!table.lookup(&["discord"]).unwrap().mpris);
+	}
+
+	#[test]
+	fn lookup_tries_every_name_ignoring_case() {
+		let table = AppTable::parse(r#"{ "Strawberry": { "mpris": true, "icon": "/usr/share/pixmaps/strawberry.png" } }"#).unwrap();
+
+		let strawberry = table.lookup(&["", "unknown", "STRAWBERRY"]).unwrap();
+		assert!(strawberry.mpris);
+		assert_eq!(strawberry.bus_name, None);
+		assert_eq!(strawberry.icon_path().as_deref(), Some("/usr/share/pixmaps/strawberry.png"));
+		assert_eq!(table.lookup(&["tidal-hifi"]), None);
+	}
+
+	#[test]
+	fn lookup_falls_back_to_keys_within_the_name() {
+		let table = AppTable::parse(include_str!("../assets/apps.json")).unwrap();
+
+		let firefox = table.lookup(&["firefox-esr"]).unwrap();
+		assert!(firefox.mpris);
+		assert_eq!(firefox.bus_name.as_deref(), Some("firefox"));
+
+		let chrome = table.lookup(&["", "google-chrome-stable", "Google Chrome"]).unwrap();
+		assert_eq!(chrome.bus_name.as_deref(), Some("chromium"));
+		assert_eq!(chrome.icon_path().as_deref(), Some("icons/chrome.png"));
+
+		// An exact key wins over one found within another name
+		assert_eq!(table.lookup(&["chromium-browser", "chromium"]).unwrap().bus_name, None);
+	}
+}
@@ -0,0 +1,263 @@
+//! Album art shrunk to the device's resolution before it is sent, instead of pushing the player's
+//! full-size image through the WebSocket for every key.
+//!
+//! Configured through the environment:
+//! - `PLAYMIX_ART_SIZE`: edge length in pixels (default 144)
+//! - `PLAYMIX_ART_CORNER_RADIUS`: rounded corner radius in pixels (default 0)
+//! - `PLAYMIX_ART_FORMAT`: `jpeg` (default) or `png`
+//! - `PLAYMIX_ART_QUALITY`: JPEG quality from 1 to 100 (default 85)
+//! - `PLAYMIX_ART_BADGE`: set to 0 to leave the playback state badge off the Play/Pause key's art
+
+use anyhow::Result;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+use once_cell::sync::Lazy;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtFormat {
+	Png,
+	Jpeg { quality: u8 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArtOptions {
+	pub size: u32,
+	pub corner_radius: u32,
+	pub format: ArtFormat,
+	pub badge: bool,
+}
+
+impl Default for ArtOptions {
+	fn default() -> Self {
+		ArtOptions { size: 144, corner_radius: 0, format: ArtFormat::Jpeg { quality: 85 }, badge: true }
+	}
+}
+
+impl ArtOptions {
+	pub fn from_env() -> Self {
+		let defaults = ArtOptions::default();
+		let number = |key: &str| std::env::var(key).ok().and_then(|value| value.trim().parse::<u32>().ok());
+		let format = match std::env::var("PLAYMIX_ART_FORMAT").as_deref().map(str::to_lowercase).as_deref() {
+			Ok("png") => ArtFormat::Png,
+			_ => ArtFormat::Jpeg {
+				quality: number("PLAYMIX_ART_QUALITY").map_or(85, |quality| quality.clamp(1, 100) as u8),
+			},
+		};
+		ArtOptions {
+			size: number("PLAYMIX_ART_SIZE").filter(|&size| size > 0).unwrap_or(defaults.size),
+			corner_radius: number("PLAYMIX_ART_CORNER_RADIUS").unwrap_or(defaults.corner_radius),
+			format,
+			badge: std::env::var("PLAYMIX_ART_BADGE").map_or(defaults.badge, |value| value != "0" && value != "false"),
+		}
+	}
+}
+
+pub static ART_OPTIONS: Lazy<ArtOptions> = Lazy::new(ArtOptions::from_env);
+
+/// Playback state drawn in the corner of the art
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Badge {
+	Playing,
+	Paused,
+	Stopped,
+}
+
+impl Badge {
+	/// The badge for an MPRIS `PlaybackStatus`
+	pub fn from_status(status: &str) -> Option<Self> {
+		match status {
+			"Playing" => Some(Badge::Playing),
+			"Paused" => Some(Badge::Paused),
+			"Stopped" => Some(Badge::Stopped),
+			_ => None,
+		}
+	}
+
+	/// Whether the glyph covers a point, relative to the badge center and scaled by its radius
+	fn covers(self, x: f32, y: f32) -> bool {
+		match self {
+			Badge::Playing => x >= -0.3 && y.abs() <= 0.4 * (0.45 - x) / 0.75,
+			Badge::Paused => y.abs() <= 0.4 && ((-0.35..=-0.1).contains(&x) || (0.1..=0.35).contains(&x)),
+			Badge::Stopped => x.abs() <= 0.3 && y.abs() <= 0.3,
+		}
+	}
+}
+
+/// Coverage of a pixel by a circle edge at `distance` from its center, for antialiasing
+fn coverage(radius: f32, distance: f32) -> f32 {
+	(radius - distance + 0.5).clamp(0.0, 1.0)
+}
+
+fn blend(pixel: &mut Rgba<u8>, color: [u8; 3], alpha: f32) {
+	let [r, g, b, a] = pixel.0;
+	let mix = |under: u8, over: u8| (over as f32 * alpha + under as f32 * (1.0 - alpha)).round() as u8;
+	let out_alpha = alpha + a as f32 / 255.0 * (1.0 - alpha);
+	pixel.0 = [mix(r, color[0]), mix(g, color[1]), mix(b, color[2]), (out_alpha * 255.0).round() as u8];
+}
+
+fn round_corners(image: &mut RgbaImage, radius: u32) {
+	let (width, height) = image.dimensions();
+	let radius = radius.min(width / 2).min(height / 2) as f32;
+	for (x, y, pixel) in image.enumerate_pixels_mut() {
+		let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+		let cx = px.clamp(radius, width as f32 - radius);
+		let cy = py.clamp(radius, height as f32 - radius);
+		let distance = ((px - cx).powi(2) + (py - cy).powi(2)).sqrt();
+		if distance > 0.0 {
+			pixel.0[3] = (pixel.0[3] as f32 * coverage(radius, distance)).round() as u8;
+		}
+	}
+}
+
+fn draw_badge(image: &mut RgbaImage, badge: Badge) {
+	let size = image.width().min(image.height()) as f32;
+	let radius = size * 0.18;
+	let center_x = image.width() as f32 - size * 0.04 - radius;
+	let center_y = image.height() as f32 - size * 0.04 - radius;
+	for (x, y, pixel) in image.enumerate_pixels_mut() {
+		let dx = x as f32 + 0.5 - center_x;
+		let dy = y as f32 + 0.5 - center_y;
+		let disc = coverage(radius, (dx * dx + dy * dy).sqrt());
+		if disc == 0.0 {
+			continue;
+		}
+		blend(pixel, [0, 0, 0], disc * 0.7);
+		if badge.covers(dx / radius, dy / radius) {
+			blend(pixel, [255, 255, 255], 1.0);
+		}
+	}
+}
+
+/// Edge length the mute glyph is laid out for; it is scaled to the actual image
+const MUTE_GLYPH_SIZE: f32 = 144.0;
+
+/// Whether the crossed-out speaker covers a point, in the coordinates of a [`MUTE_GLYPH_SIZE`] square
+fn mute_glyph_covers(x: f32, y: f32) -> bool {
+	let body = (52.0..=62.0).contains(&x) && (62.0..=82.0).contains(&y);
+	let cone = (62.0..=75.0).contains(&x) && (y - 72.0).abs() <= 10.0 + (x - 62.0) * 11.0 / 13.0;
+	// Distance to a stroke of the cross, with round caps
+	let stroke = |(x1, y1): (f32, f32), (x2, y2): (f32, f32)| {
+		let (dx, dy) = (x2 - x1, y2 - y1);
+		let t = (((x - x1) * dx + (y - y1) * dy) / (dx * dx + dy * dy)).clamp(0.0, 1.0);
+		((x - x1 - t * dx).powi(2) + (y - y1 - t * dy).powi(2)).sqrt() <= 2.5
+	};
+	body || cone || stroke((82.0, 63.0), (98.0, 81.0)) || stroke((98.0, 63.0), (82.0, 81.0))
+}
+
+/// Fit an image into a `size` pixel square, dim it and put a crossed-out speaker in the middle.
+/// Returns the image encoded as PNG.
+pub fn mute_overlay(bytes: &[u8], size: u32) -> Result<Vec<u8>> {
+	let art = image::load_from_memory(bytes)?.resize(size, size, FilterType::Lanczos3).to_rgba8();
+	let mut image = RgbaImage::new(size, size);
+	image::imageops::overlay(&mut image, &art, ((size - art.width()) / 2).into(), ((size - art.height()) / 2).into());
+
+	let scale = MUTE_GLYPH_SIZE / size as f32;
+	let center = size as f32 / 2.0;
+	for (x, y, pixel) in image.enumerate_pixels_mut() {
+		blend(pixel, [0, 0, 0], 0.55);
+		let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+		let disc = coverage(34.0 / scale, ((px - center).powi(2) + (py - center).powi(2)).sqrt());
+		if disc > 0.0 {
+			blend(pixel, [211, 47, 47], disc);
+		}
+		if mute_glyph_covers(px * scale, py * scale) {
+			blend(pixel, [255, 255, 255], 1.0);
+		}
+	}
+
+	let mut encoded = Vec::new();
+	DynamicImage::ImageRgba8(image).write_to(&mut std::io::Cursor::new(&mut encoded), ImageFormat::Png)?;
+	Ok(encoded)
+}
+
+/// Center-crop and scale art to a square of `options.size`, round its corners and draw `badge`.
+/// Returns the MIME type and the encoded image.
+pub fn render_art(bytes: &[u8], options: &ArtOptions, badge: Option<Badge>) -> Result<(&'static str, Vec<u8>)> {
+	let art = image::load_from_memory(bytes)?;
+	let mut image = art.resize_to_fill(options.size, options.size, FilterType::Lanczos3).to_rgba8();
+	if options.corner_radius > 0 {
+		round_corners(&mut image, options.corner_radius);
+	}
+	if let Some(badge) = badge {
+		draw_badge(&mut image, badge);
+	}
+
+	let mut encoded = Vec::new();
+	match options.format {
+		ArtFormat::Png => {
+			DynamicImage::ImageRgba8(image).write_to(&mut std::io::Cursor::new(&mut encoded), ImageFormat::Png)?;
+			Ok(("image/png", encoded))
+		}
+		ArtFormat::Jpeg { quality } => {
+			// JPEG has no transparency; rounded corners end up black like the keys around them
+			let mut flattened = RgbaImage::from_pixel(image.width(), image.height(), Rgba([0, 0, 0, 255]));
+			image::imageops::overlay(&mut flattened, &image, 0, 0);
+			let rgb = DynamicImage::ImageRgba8(flattened).to_rgb8();
+			JpegEncoder::new_with_quality(&mut encoded, quality).encode_image(&rgb)?;
+			Ok(("image/jpeg", encoded))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn encoded(width: u32, height: u32) -> Vec<u8> {
+		let mut bytes = Vec::new();
+		DynamicImage::ImageRgba8(RgbaImage::from_pixel(width, height, Rgba([200, 40, 40, 255])))
+			.write_to(&mut std::io::Cursor::new(&mut bytes), ImageFormat::Png)
+			.unwrap();
+		bytes
+	}
+
+	#[test]
+	fn crops_and_scales_to_a_square() {
+		let options = ArtOptions { size: 72, format: ArtFormat::Png, ..Default::default() };
+		let (mime, bytes) = render_art(&encoded(300, 200), &options, None).unwrap();
+
+		assert_eq!(mime, "image/png");
+		let image = image::load_from_memory(&bytes).unwrap();
+		assert_eq!((image.width(), image.height()), (72, 72));
+	}
+
+	#[test]
+	fn rounds_corners_and_draws_the_badge() {
+		let options = ArtOptions { size: 100, corner_radius: 20, format: ArtFormat::Png, ..Default::default() };
+		let (_, bytes) = render_art(&encoded(100, 100), &options, Some(Badge::Stopped)).unwrap();
+		let image = image::load_from_memory(&bytes).unwrap().to_rgba8();
+
+		assert_eq!(image.get_pixel(0, 0).0[3], 0);
+		assert_eq!(image.get_pixel(50, 50).0, [200, 40, 40, 255]);
+		// The stop square sits in the middle of the badge in the bottom-right corner
+		assert_eq!(image.get_pixel(78, 78).0, [255, 255, 255, 255]);
+	}
+
+	#[test]
+	fn mute_overlay_dims_the_image_around_the_speaker() {
+		let image = image::load_from_memory(&mute_overlay(&encoded(100, 50), 144).unwrap()).unwrap().to_rgba8();
+
+		assert_eq!(image.dimensions(), (144, 144));
+		// Letterboxed above and below, dimmed, with the glyph on the red disc
+		assert_eq!(image.get_pixel(72, 5).0, [0, 0, 0, 140]);
+		assert_eq!(image.get_pixel(20, 72).0, [90, 18, 18, 255]);
+		assert_eq!(image.get_pixel(56, 72).0, [255, 255, 255, 255]);
+		assert_eq!(image.get_pixel(72, 45).0, [211, 47, 47, 255]);
+
+		// Smaller keys get the same glyph scaled down
+		let small = image::load_from_memory(&mute_overlay(&encoded(100, 50), 72).unwrap()).unwrap().to_rgba8();
+		assert_eq!(small.dimensions(), (72, 72));
+		assert_eq!(small.get_pixel(28, 36).0, [255, 255, 255, 255]);
+		assert_eq!(small.get_pixel(36, 22).0, [211, 47, 47, 255]);
+	}
+
+	#[test]
+	fn jpeg_output_has_the_requested_size() {
+		let (mime, bytes) = render_art(&encoded(50, 80), &ArtOptions::default(), Some(Badge::Playing)).unwrap();
+
+		assert_eq!(mime, "image/jpeg");
+		let image = image::load_from_memory(&bytes).unwrap();
+		assert_eq!((image.width(), image.height()), (144, 144));
+	}
+}
@@ -0,0 +1,409 @@
+//! Sound server abstraction shared by the audio actions.
+//!
+//! The native protocol client is used by default. Set `PLAYMIX_AUDIO_BACKEND=pactl` to shell out to
+//! `pactl`/`wpctl` instead, or `PLAYMIX_AUDIO_BACKEND=mock` to run without any sound server.
+
+use super::pactl::CommandBackend;
+use super::pulse::PulseClient;
+
+use anyhow::{Result, anyhow};
+use once_cell::sync::Lazy;
+use openaction::async_trait;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+/// 100% volume
+pub const VOLUME_NORM: u32 = 0x10000;
+pub const VOLUME_MAX: u32 = u32::MAX / 2;
+
+/// Special sink name that always resolves to the current default sink
+pub const DEFAULT_SINK: &str = "@DEFAULT_SINK@";
+
+/// Special source name that always resolves to the current default source
+pub const DEFAULT_SOURCE: &str = "@DEFAULT_SOURCE@";
+
+/// A playback stream as reported by the server
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SinkInput {
+	pub index: u32,
+	pub name: Option<String>,
+	pub client: Option<u32>,
+	pub sink: u32,
+	pub volume: Vec<u32>,
+	pub muted: bool,
+	pub corked: bool,
+	pub properties: HashMap<String, String>,
+}
+
+impl SinkInput {
+	pub fn property(&self, key: &str) -> Option<&str> {
+		self.properties.get(key).map(String::as_str)
+	}
+
+	pub fn app_name(&self) -> &str {
+		self.property("application.name").unwrap_or("Unknown")
+	}
+
+	pub fn process_binary(&self) -> &str {
+		self.property("application.process.binary").unwrap_or("")
+	}
+
+	/// Identifies the application behind this stream, stable across stream churn and restarts.
+	/// Prefers the Flatpak app ID, then the declared application ID, the binary and the name.
+	pub fn app_id(&self) -> String {
+		["pipewire.access.portal.app_id", "application.id", "application.process.binary", "application.name"]
+			.into_iter()
+			.find_map(|key| self.property(key).filter(|value| !value.is_empty()))
+			.unwrap_or("unknown")
+			.to_lowercase()
+	}
+}
+
+/// A sink or source as reported by the server
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Device {
+	pub index: u32,
+	pub name: String,
+	pub description: Option<String>,
+	pub volume: Vec<u32>,
+	pub muted: bool,
+	pub properties: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ServerInfo {
+	pub default_sink: Option<String>,
+	pub default_source: Option<String>,
+}
+
+/// What kind of server object an [`AudioEvent`] is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Facility {
+	Sink,
+	Source,
+	SinkInput,
+	SourceOutput,
+	Server,
+	Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+	New,
+	Change,
+	Remove,
+}
+
+/// Change notification pushed by the sound server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioEvent {
+	pub facility: Facility,
+	pub kind: EventKind,
+	pub index: u32,
+}
+
+/// Average channel volume as a percentage of [`VOLUME_NORM`]
+pub fn volume_percent(volume: &[u32]) -> u32 {
+	if volume.is_empty() {
+		return 0;
+	}
+	let sum: u64 = volume.iter().map(|&v| v as u64).sum();
+	let average = sum / volume.len() as u64;
+	((average * 100 + VOLUME_NORM as u64 / 2) / VOLUME_NORM as u64) as u32
+}
+
+/// Shift every channel by `delta_percent`, clamped to `limit_percent` (like `pactl ... +5%`)
+pub fn adjust_volume(volume: &[u32], delta_percent: i32, limit_percent: Option<u32>) -> Vec<u32> {
+	let delta = delta_percent as i64 * VOLUME_NORM as i64 / 100;
+	let limit = limit_percent
+		.map(|percent| (percent as i64 * VOLUME_NORM as i64 / 100).min(VOLUME_MAX as i64))
+		.unwrap_or(VOLUME_MAX as i64);
+	volume
+		.iter()
+		.map(|&v| (v as i64 + delta).clamp(0, limit) as u32)
+		.collect()
+}
+
+/// Everything the actions need from the sound server
+#[async_trait]
+pub trait AudioBackend: Send + Sync {
+	async fn server_info(&self) -> Result<ServerInfo>;
+
+	async fn sink_inputs(&self) -> Result<Vec<SinkInput>>;
+
+	async fn sink_input(&self, index: u32) -> Result<SinkInput> {
+		self.sink_inputs()
+			.await?
+			.into_iter()
+			.find(|sink_input| sink_input.index == index)
+			.ok_or_else(|| anyhow!("Sink input {} not found", index))
+	}
+
+	async fn set_sink_input_volume(&self, index: u32, volume: &[u32]) -> Result<()>;
+
+	async fn set_sink_input_mute(&self, index: u32, muted: bool) -> Result<()>;
+
+	/// Route a sink input to another sink
+	async fn move_sink_input(&self, index: u32, sink: &str) -> Result<()>;
+
+	async fn sinks(&self) -> Result<Vec<Device>>;
+
+	async fn sources(&self) -> Result<Vec<Device>>;
+
+	/// Look up a sink by name, resolving [`DEFAULT_SINK`]
+	async fn sink(&self, name: &str) -> Result<Device> {
+		let name = if name == DEFAULT_SINK {
+			self.server_info()
+				.await?
+				.default_sink
+				.ok_or_else(|| anyhow!("No default sink"))?
+		} else {
+			name.to_owned()
+		};
+		self.sinks()
+			.await?
+			.into_iter()
+			.find(|sink| sink.name == name)
+			.ok_or_else(|| anyhow!("Sink {} not found", name))
+	}
+
+	async fn set_sink_volume(&self, name: &str, volume: &[u32]) -> Result<()>;
+
+	async fn set_sink_mute(&self, name: &str, muted: bool) -> Result<()>;
+
+	async fn set_default_sink(&self, name: &str) -> Result<()>;
+
+	/// Look up a source by name, resolving [`DEFAULT_SOURCE`]
+	async fn source(&self, name: &str) -> Result<Device> {
+		let name = if name == DEFAULT_SOURCE {
+			self.server_info()
+				.await?
+				.default_source
+				.ok_or_else(|| anyhow!("No default source"))?
+		} else {
+			name.to_owned()
+		};
+		self.sources()
+			.await?
+			.into_iter()
+			.find(|source| source.name == name)
+			.ok_or_else(|| anyhow!("Source {} not found", name))
+	}
+
+	async fn set_source_volume(&self, name: &str, volume: &[u32]) -> Result<()>;
+
+	async fn set_source_mute(&self, name: &str, muted: bool) -> Result<()>;
+
+	/// Start receiving change notifications. The channel closes when the server connection is lost.
+	async fn subscribe(&self) -> Result<UnboundedReceiver<AudioEvent>>;
+
+	/// Change a sink input's volume by `delta_percent`, returning the new percentage
+	async fn change_sink_input_volume(&self, index: u32, delta_percent: i32, limit_percent: Option<u32>) -> Result<u32> {
+		let sink_input = self.sink_input(index).await?;
+		let volume = adjust_volume(&sink_input.volume, delta_percent, limit_percent);
+		self.set_sink_input_volume(index, &volume).await?;
+		Ok(volume_percent(&volume))
+	}
+
+	/// Change a sink's volume by `delta_percent`, never going above `limit_percent`
+	async fn change_sink_volume(&self, name: &str, delta_percent: i32, limit_percent: Option<u32>) -> Result<u32> {
+		let sink = self.sink(name).await?;
+		let volume = adjust_volume(&sink.volume, delta_percent, limit_percent);
+		self.set_sink_volume(name, &volume).await?;
+		Ok(volume_percent(&volume))
+	}
+
+	/// Change a source's volume by `delta_percent`, never going above `limit_percent`
+	async fn change_source_volume(&self, name: &str, delta_percent: i32, limit_percent: Option<u32>) -> Result<u32> {
+		let source = self.source(name).await?;
+		let volume = adjust_volume(&source.volume, delta_percent, limit_percent);
+		self.set_source_volume(name, &volume).await?;
+		Ok(volume_percent(&volume))
+	}
+}
+
+/// The backend every action talks to, chosen once at startup
+pub static AUDIO: Lazy<Box<dyn AudioBackend>> = Lazy::new(|| {
+	match std::env::var("PLAYMIX_AUDIO_BACKEND").as_deref() {
+		Ok("pactl") => {
+			log::info!("Using pactl/wpctl audio backend");
+			Box::new(CommandBackend)
+		}
+		Ok("mock") => {
+			log::info!("Using mock audio backend");
+			Box::new(MockBackend::default())
+		}
+		_ => Box::new(PulseClient::default()),
+	}
+});
+
+/// In-memory mixer state for [`MockBackend`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockState {
+	pub server: ServerInfo,
+	pub sink_inputs: Vec<SinkInput>,
+	pub sinks: Vec<Device>,
+	pub sources: Vec<Device>,
+}
+
+impl Default for MockState {
+	fn default() -> Self {
+		MockState {
+			server: ServerInfo { default_sink: Some("mock_sink".to_owned()), default_source: None },
+			sink_inputs: Vec::new(),
+			sinks: vec![Device {
+				index: 0,
+				name: "mock_sink".to_owned(),
+				description: Some("Mock Output".to_owned()),
+				volume: vec![VOLUME_NORM / 2; 2],
+				..Default::default()
+			}],
+			sources: Vec::new(),
+		}
+	}
+}
+
+/// Backend that keeps everything in memory, for tests and running without a sound server
+#[derive(Default)]
+pub struct MockBackend {
+	state: Mutex<MockState>,
+	subscribers: Mutex<Vec<UnboundedSender<AudioEvent>>>,
+}
+
+impl MockBackend {
+	pub fn state(&self) -> MutexGuard<'_, MockState> {
+		self.state.lock().unwrap()
+	}
+
+	fn with_sink_input(&self, index: u32, f: impl FnOnce(&mut SinkInput)) -> Result<()> {
+		let mut state = self.state();
+		let sink_input = state
+			.sink_inputs
+			.iter_mut()
+			.find(|sink_input| sink_input.index == index)
+			.ok_or_else(|| anyhow!("Sink input {} not found", index))?;
+		f(sink_input);
+		drop(state);
+		self.notify(Facility::SinkInput, index);
+		Ok(())
+	}
+
+	fn with_sink(&self, name: &str, f: impl FnOnce(&mut Device)) -> Result<()> {
+		let mut state = self.state();
+		let name = if name == DEFAULT_SINK {
+			state.server.default_sink.clone().unwrap_or_default()
+		} else {
+			name.to_owned()
+		};
+		let sink = state
+			.sinks
+			.iter_mut()
+			.find(|sink| sink.name == name)
+			.ok_or_else(|| anyhow!("Sink {} not found", name))?;
+		f(sink);
+		let index = sink.index;
+		drop(state);
+		self.notify(Facility::Sink, index);
+		Ok(())
+	}
+
+	fn with_source(&self, name: &str, f: impl FnOnce(&mut Device)) -> Result<()> {
+		let mut state = self.state();
+		let name = if name == DEFAULT_SOURCE {
+			state.server.default_source.clone().unwrap_or_default()
+		} else {
+			name.to_owned()
+		};
+		let source = state
+			.sources
+			.iter_mut()
+			.find(|source| source.name == name)
+			.ok_or_else(|| anyhow!("Source {} not found", name))?;
+		f(source);
+		let index = source.index;
+		drop(state);
+		self.notify(Facility::Source, index);
+		Ok(())
+	}
+
+	/// Tell subscribers an object changed, like the real server does after every write
+	fn notify(&self, facility: Facility, index: u32) {
+		let event = AudioEvent { facility, kind: EventKind::Change, index };
+		self.subscribers
+			.lock()
+			.unwrap()
+			.retain(|subscriber| subscriber.send(event).is_ok());
+	}
+}
+
+#[async_trait]
+impl AudioBackend for MockBackend {
+	async fn server_info(&self) -> Result<ServerInfo> {
+		Ok(self.state().server.clone())
+	}
+
+	async fn sink_inputs(&self) -> Result<Vec<SinkInput>> {
+		Ok(self.state().sink_inputs.clone())
+	}
+
+	async fn set_sink_input_volume(&self, index: u32, volume: &[u32]) -> Result<()> {
+		self.with_sink_input(index, |sink_input| sink_input.volume = volume.to_vec())
+	}
+
+	async fn set_sink_input_mute(&self, index: u32, muted: bool) -> Result<()> {
+		self.with_sink_input(index, |sink_input| sink_input.muted = muted)
+	}
+
+	async fn move_sink_input(&self, index: u32, sink: &str) -> Result<()> {
+		let sink = self
+			.state()
+			.sinks
+			.iter()
+			.find(|device| device.name == sink)
+			.map(|device| device.index)
+			.ok_or_else(|| anyhow!("Sink {} not found", sink))?;
+		self.with_sink_input(index, |sink_input| sink_input.sink = sink)
+	}
+
+	async fn sinks(&self) -> Result<Vec<Device>> {
+		Ok(self.state().sinks.clone())
+	}
+
+	async fn sources(&self) -> Result<Vec<Device>> {
+		Ok(self.state().sources.clone())
+	}
+
+	async fn set_sink_volume(&self, name: &str, volume: &[u32]) -> Result<()> {
+		self.with_sink(name, |sink| sink.volume = volume.to_vec())
+	}
+
+	async fn set_sink_mute(&self, name: &str, muted: bool) -> Result<()> {
+		self.with_sink(name, |sink| sink.muted = muted)
+	}
+
+	async fn set_default_sink(&self, name: &str) -> Result<()> {
+		let mut state = self.state();
+		if !state.sinks.iter().any(|sink| sink.name == name) {
+			return Err(anyhow!("Sink {} not found", name));
+		}
+		state.server.default_sink = Some(name.to_owned());
+		drop(state);
+		self.notify(Facility::Server, 0);
+		Ok(())
+	}
+
+	async fn set_source_volume(&self, name: &str, volume: &[u32]) -> Result<()> {
+		self.with_source(name, |source| source.volume = volume.to_vec())
+	}
+
+	async fn set_source_mute(&self, name: &str, muted: bool) -> Result<()> {
+		self.with_source(name, |source| source.muted = muted)
+	}
+
+	async fn subscribe(&self) -> Result<UnboundedReceiver<AudioEvent>> {
+		let (sender, receiver) = unbounded_channel();
+		self.subscribers.lock().unwrap().push(sender);
+		Ok(receiver)
+	}
+}
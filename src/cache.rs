@@ -0,0 +1,158 @@
+//! Cache of the data URLs built for album art and icons, so repaints don't re-read or re-download
+//! the same image. Downloaded art is also kept on disk across restarts, in `$XDG_CACHE_HOME/playmix`
+//! unless `PLAYMIX_CACHE_DIR` points elsewhere (set it empty to keep nothing on disk).
+
+use lru::LruCache;
+use once_cell::sync::Lazy;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const MAX_ENTRIES: usize = 64;
+const MAX_BYTES: usize = 32 * 1024 * 1024;
+const MAX_DISK_ENTRIES: usize = 256;
+
+/// Least recently used data URLs, limited by count and total size
+pub struct ImageCache {
+	entries: LruCache<String, String>,
+	bytes: usize,
+	max_bytes: usize,
+}
+
+impl ImageCache {
+	pub fn new(max_entries: usize, max_bytes: usize) -> Self {
+		ImageCache {
+			entries: LruCache::new(NonZeroUsize::new(max_entries).unwrap_or(NonZeroUsize::MIN)),
+			bytes: 0,
+			max_bytes,
+		}
+	}
+
+	pub fn get(&mut self, key: &str) -> Option<String> {
+		self.entries.get(key).cloned()
+	}
+
+	pub fn insert(&mut self, key: String, data_url: String) {
+		if data_url.len() > self.max_bytes {
+			return;
+		}
+		if let Some(old) = self.entries.pop(&key) {
+			self.bytes -= old.len();
+		}
+		while self.bytes + data_url.len() > self.max_bytes {
+			let Some((_, evicted)) = self.entries.pop_lru() else {
+				break;
+			};
+			self.bytes -= evicted.len();
+		}
+		self.bytes += data_url.len();
+		if let Some((_, evicted)) = self.entries.push(key, data_url) {
+			self.bytes -= evicted.len();
+		}
+	}
+}
+
+pub static IMAGE_CACHE: Lazy<Mutex<ImageCache>> = Lazy::new(|| Mutex::new(ImageCache::new(MAX_ENTRIES, MAX_BYTES)));
+
+/// Cache key for `url`. Local files include their modification time so edited icons show up.
+pub fn cache_key(url: &str) -> String {
+	let modified = url
+		.strip_prefix("file://")
+		.and_then(|path| std::fs::metadata(path).ok()?.modified().ok())
+		.and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok());
+	match modified {
+		Some(modified) => format!("{}#{}", url, modified.as_millis()),
+		None => url.to_owned(),
+	}
+}
+
+fn disk_cache_dir() -> Option<PathBuf> {
+	match std::env::var_os("PLAYMIX_CACHE_DIR") {
+		Some(dir) if dir.is_empty() => None,
+		Some(dir) => Some(PathBuf::from(dir)),
+		None => std::env::var_os("XDG_CACHE_HOME")
+			.map(PathBuf::from)
+			.or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+			.map(|dir| dir.join("playmix")),
+	}
+}
+
+/// 64-bit FNV-1a. Unlike the standard library's hasher its output never changes, so files cached
+/// by an earlier build are still found.
+fn fnv1a(bytes: &[u8]) -> u64 {
+	bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+fn disk_path(url: &str) -> Option<PathBuf> {
+	Some(disk_cache_dir()?.join(format!("{:016x}", fnv1a(url.as_bytes()))))
+}
+
+/// A downloaded image kept from an earlier run
+pub fn load_from_disk(url: &str) -> Option<Vec<u8>> {
+	std::fs::read(disk_path(url)?).ok()
+}
+
+/// Keep a downloaded image for later runs, dropping the oldest ones past the limit
+pub fn store_on_disk(url: &str, bytes: &[u8]) {
+	let Some(path) = disk_path(url) else {
+		return;
+	};
+	let Some(dir) = path.parent() else {
+		return;
+	};
+	if let Err(e) = std::fs::create_dir_all(dir).and_then(|_| std::fs::write(&path, bytes)) {
+		log::warn!("Failed to cache {} on disk: {}", url, e);
+		return;
+	}
+
+	let Ok(entries) = std::fs::read_dir(dir) else {
+		return;
+	};
+	let mut files: Vec<(std::time::SystemTime, PathBuf)> = entries
+		.filter_map(|entry| {
+			let entry = entry.ok()?;
+			Some((entry.metadata().ok()?.modified().ok()?, entry.path()))
+		})
+		.collect();
+	if files.len() > MAX_DISK_ENTRIES {
+		files.sort();
+		for (_, old) in &files[..files.len() - MAX_DISK_ENTRIES] {
+			let _ = std::fs::remove_file(old);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn evicts_least_recently_used_entries_past_the_limits() {
+		let mut cache = ImageCache::new(3, 10);
+		cache.insert("a".to_owned(), "aaaa".to_owned());
+		cache.insert("b".to_owned(), "bbbb".to_owned());
+		assert_eq!(cache.get("a").as_deref(), Some("aaaa"));
+
+		// Too big for the remaining bytes: "b" is the least recently used
+		cache.insert("c".to_owned(), "cccc".to_owned());
+		assert_eq!(cache.get("b"), None);
+		assert_eq!(cache.bytes, 8);
+
+		// Larger than the whole cache
+		cache.insert("d".to_owned(), "d".repeat(11));
+		assert_eq!(cache.get("d"), None);
+
+		cache.insert("a".to_owned(), "a".to_owned());
+		cache.insert("e".to_owned(), "e".to_owned());
+		cache.insert("f".to_owned(), "f".to_owned());
+		assert_eq!(cache.get("c"), None);
+		assert_eq!(cache.bytes, 3);
+	}
+
+	#[test]
+	fn disk_names_use_a_stable_hash() {
+		assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+		assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+		assert_eq!(fnv1a(b"foobar"), 0x85944171f73967e8);
+	}
+}
@@ -0,0 +1,218 @@
+//! Application icons resolved the way the desktop does: the stream's `application.icon_name`, the
+//! `Icon=` key of the app's `.desktop` file, then the current icon theme with hicolor and
+//! `/usr/share/pixmaps` as fallbacks. SVG icons are rendered to PNG for the device.
+
+use super::audio::SinkInput;
+use super::cache::{IMAGE_CACHE, cache_key};
+
+use anyhow::{Result, anyhow};
+use base64::{Engine as _, engine::general_purpose};
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Size icons are looked up and rendered at
+const ICON_SIZE: u16 = 128;
+
+/// The icon theme configured in GNOME or KDE, hicolor otherwise
+static ICON_THEME: Lazy<String> = Lazy::new(|| {
+	let theme = freedesktop_icons::default_theme_gtk().or_else(kde_icon_theme).unwrap_or_else(|| "hicolor".to_owned());
+	log::info!("Using icon theme {}", theme);
+	theme
+});
+
+fn kde_icon_theme() -> Option<String> {
+	let config = std::env::var_os("XDG_CONFIG_HOME")
+		.map(PathBuf::from)
+		.or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+	let kdeglobals = std::fs::read_to_string(config.join("kdeglobals")).ok()?;
+	ini_value(&kdeglobals, "Icons", "Theme")
+}
+
+/// Value of `key` in `[group]` of an INI style file such as a `.desktop` file
+fn ini_value(contents: &str, group: &str, key: &str) -> Option<String> {
+	let header = format!("[{}]", group);
+	let mut in_group = false;
+	for line in contents.lines().map(str::trim) {
+		if line.starts_with('[') {
+			in_group = line == header;
+		} else if in_group
+			&& let Some((name, value)) = line.split_once('=')
+			&& name.trim() == key
+		{
+			return Some(value.trim().to_owned()).filter(|value| !value.is_empty());
+		}
+	}
+	None
+}
+
+/// `applications` directories of every XDG data directory, including Flatpak exports
+fn application_dirs() -> Vec<PathBuf> {
+	let home = std::env::var_os("HOME").map(PathBuf::from);
+	let data_home = std::env::var_os("XDG_DATA_HOME")
+		.map(PathBuf::from)
+		.or_else(|| home.as_ref().map(|home| home.join(".local/share")));
+	let data_dirs = std::env::var("XDG_DATA_DIRS").unwrap_or_else(|_| "/usr/local/share:/usr/share".to_owned());
+
+	data_home
+		.into_iter()
+		.chain(home.map(|home| home.join(".local/share/flatpak/exports/share")))
+		.chain(std::iter::once(PathBuf::from("/var/lib/flatpak/exports/share")))
+		.chain(data_dirs.split(':').filter(|dir| !dir.is_empty()).map(PathBuf::from))
+		.map(|dir| dir.join("applications"))
+		.collect()
+}
+
+/// The `Icon=` of the first `<name>.desktop` file found for any of `names`
+fn desktop_file_icon(names: &[&str]) -> Option<String> {
+	let dirs = application_dirs();
+	names.iter().filter(|name| !name.is_empty()).find_map(|name| {
+		dirs.iter().find_map(|dir| {
+			let contents = std::fs::read_to_string(dir.join(format!("{}.desktop", name))).ok()?;
+			ini_value(&contents, "Desktop Entry", "Icon")
+		})
+	})
+}
+
+/// Resolve an icon name (or absolute path) to a file
+fn find_icon(name: &str) -> Option<PathBuf> {
+	if name.starts_with('/') {
+		return Some(PathBuf::from(name)).filter(|path| path.exists());
+	}
+	freedesktop_icons::lookup(name).with_size(ICON_SIZE).with_theme(&ICON_THEME).with_cache().find()
+}
+
+/// Candidate icon names for an app, most specific first
+fn icon_names(sink_input: Option<&SinkInput>, app_id: &str) -> Vec<String> {
+	let mut names = Vec::new();
+	let mut desktop_ids = vec![app_id.to_owned()];
+	if let Some(sink_input) = sink_input {
+		names.extend(sink_input.property("application.icon_name").map(str::to_owned));
+		for key in ["pipewire.access.portal.app_id", "application.id", "application.process.binary"] {
+			desktop_ids.extend(sink_input.property(key).map(str::to_owned));
+		}
+	}
+	let ids: Vec<&str> = desktop_ids.iter().map(String::as_str).collect();
+	names.extend(desktop_file_icon(&ids));
+	names.extend(desktop_ids.iter().cloned());
+	// Each name is looked up in the theme only once, at its first position
+	let mut seen = HashSet::new();
+	names.retain(|name| !name.is_empty() && seen.insert(name.clone()));
+	names
+}
+
+/// Render an SVG file to a PNG of [`ICON_SIZE`] pixels square
+fn render_svg(path: &Path) -> Result<Vec<u8>> {
+	use resvg::{tiny_skia, usvg};
+
+	let tree = usvg::Tree::from_data(&std::fs::read(path)?, &usvg::Options::default())?;
+	let size = tree.size();
+	let scale = ICON_SIZE as f32 / size.width().max(size.height());
+	let mut pixmap = tiny_skia::Pixmap::new(ICON_SIZE as u32, ICON_SIZE as u32).ok_or_else(|| anyhow!("Empty pixmap"))?;
+	// Center icons that aren't square
+	let transform = tiny_skia::Transform::from_translate(
+		(ICON_SIZE as f32 - size.width() * scale) / 2.0,
+		(ICON_SIZE as f32 - size.height() * scale) / 2.0,
+	)
+	.pre_scale(scale, scale);
+	resvg::render(&tree, transform, &mut pixmap.as_mut());
+	Ok(pixmap.encode_png()?)
+}
+
+/// Data URL of an icon file, rendering SVGs to PNG
+fn icon_file_data_url(path: &Path) -> Result<String> {
+	let (mime, data) = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("svg")) {
+		("image/png", render_svg(path)?)
+	} else {
+		let data = std::fs::read(path)?;
+		let mime = infer::get(&data).map(|kind| kind.mime_type()).unwrap_or("image/png");
+		(mime, data)
+	};
+	Ok(format!("data:{};base64,{}", mime, general_purpose::STANDARD.encode(data)))
+}
+
+/// Data URL of an icon file, bundled or from the theme, rendered to PNG if it is an SVG
+pub fn icon_data_url(path: &Path) -> Option<String> {
+	// Rendered icons are kept apart from the same file fetched as it is, e.g. as album art
+	let key = format!("icon:{}", cache_key(&format!("file://{}", path.display())));
+	if let Some(data_url) = IMAGE_CACHE.lock().unwrap().get(&key) {
+		return Some(data_url);
+	}
+	let data_url = icon_file_data_url(path)
+		.inspect_err(|e| log::warn!("Failed to load icon {}: {}", path.display(), e))
+		.ok()?;
+	IMAGE_CACHE.lock().unwrap().insert(key, data_url.clone());
+	Some(data_url)
+}
+
+/// The theme icon found for each app, or `None` if it has none, so repaints skip the lookup
+static THEME_ICONS: Lazy<Mutex<HashMap<String, Option<PathBuf>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// The desktop's icon for an app as a data URL, if it has one. This reads the disk; call it from
+/// a blocking task.
+pub fn theme_icon_data_url(sink_input: Option<&SinkInput>, app_id: &str) -> Option<String> {
+	let cached = THEME_ICONS.lock().unwrap().get(app_id).cloned();
+	let path = match cached {
+		Some(path) => path,
+		None => {
+			let names = icon_names(sink_input, app_id);
+			log::info!("Looking for theme icon matching: {:?}", names);
+			let path = names.iter().find_map(|name| find_icon(name));
+			match &path {
+				Some(path) => log::info!("Found theme icon: {}", path.display()),
+				None => log::info!("No theme icon for {}", app_id),
+			}
+			THEME_ICONS.lock().unwrap().insert(app_id.to_owned(), path.clone());
+			path
+		}
+	}?;
+	icon_data_url(&path)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn reads_icon_from_the_desktop_entry_group() {
+		let desktop = "[Desktop Entry]\nName=Spotify\nIcon = spotify-client\n\n[Desktop Action new]\nIcon=other\n";
+		assert_eq!(ini_value(desktop, "Desktop Entry", "Icon").as_deref(), Some("spotify-client"));
+		assert_eq!(ini_value(desktop, "Desktop Entry", "Exec"), None);
+
+		let action_first = "[Desktop Action new]\nIcon=other\n[Desktop Entry]\nIcon=firefox\n";
+		assert_eq!(ini_value(action_first, "Desktop Entry", "Icon").as_deref(), Some("firefox"));
+	}
+
+	#[test]
+	fn candidate_names_are_tried_once_each() {
+		let sink_input = SinkInput {
+			properties: HashMap::from([
+				("application.icon_name".to_owned(), "spotify".to_owned()),
+				("application.process.binary".to_owned(), "spotify".to_owned()),
+			]),
+			..Default::default()
+		};
+		let names = icon_names(Some(&sink_input), "spotify-nonexistent-app");
+		assert_eq!(names, vec!["spotify", "spotify-nonexistent-app"]);
+	}
+
+	#[test]
+	fn renders_svg_icons_to_png() {
+		let path = std::env::temp_dir().join(format!("playmix-icon-{}.svg", std::process::id()));
+		std::fs::write(&path, r#"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="8"><rect width="16" height="8" fill="red"/></svg>"#).unwrap();
+
+		let data_url = icon_file_data_url(&path).unwrap();
+		std::fs::remove_file(&path).unwrap();
+		assert!(data_url.starts_with("data:image/png;base64,"));
+	}
+
+	#[test]
+	fn bundled_svg_icons_are_sent_as_png() {
+		// The output switch and microphone dial icons
+		for icon in ["speaker.svg", "headphones.svg", "mic.svg"] {
+			let data_url = icon_data_url(&Path::new("assets/icons").join(icon)).unwrap();
+			assert!(data_url.starts_with("data:image/png;base64,"), "{}", icon);
+		}
+	}
+}
@@ -1,43 +1,130 @@
 mod actions;
+mod art;
+mod apps;
+mod audio;
+mod cache;
+mod icons;
+mod pactl;
+mod pulse;
 
 use actions::*;
+use audio::{AUDIO, EventKind, Facility, SinkInput, volume_percent};
 
 use anyhow::Result;
 use base64::{Engine as _, engine::general_purpose};
 use futures_util::StreamExt;
 use openaction::*;
 use once_cell::sync::Lazy;
-use std::collections::HashMap;
-use std::sync::{Mutex, atomic::{AtomicBool}};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, atomic::{AtomicUsize, Ordering}};
 use zbus::fdo::DBusProxy;
 use zbus::{Connection, MatchRule, MessageStream, Proxy};
 use zbus::message::Type as MessageType;
 use zvariant::Value;
 
-pub static ENCODER_PRESSED: AtomicBool = AtomicBool::new(false);
+// Per-instance dial target
+pub static DIAL_STATES: Lazy<Mutex<HashMap<String, DialTarget>>> = Lazy::new(|| Mutex::new(HashMap::new()));
 
-// Per-instance state: (current_audio_app_index, selected_sink_input)
-pub static DIAL_STATES: Lazy<Mutex<HashMap<String, (usize, usize)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+// Last settings of each volume dial, so its target can be saved outside its own events
+pub static DIAL_SETTINGS: Lazy<Mutex<HashMap<String, HashMap<String, String>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Mute state and volume of every sink and source, so device dials only refresh when they change
+pub static DEVICE_STATES: Lazy<Mutex<HashMap<String, (bool, u32)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Every encoder that is currently held down, per instance
+pub static DIAL_PRESSES: Lazy<Mutex<DialPresses>> = Lazy::new(|| Mutex::new(DialPresses::default()));
+
+// When each dial was last rotated, to tell fast spins from slow turns
+pub static LAST_ROTATIONS: Lazy<Mutex<HashMap<String, std::time::Instant>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Every sink input the sound server currently knows about, kept current by watch_sink_inputs
+pub static SINK_INPUTS: Lazy<Mutex<HashMap<u32, SinkInput>>> = Lazy::new(|| Mutex::new(HashMap::new()));
 
 // Remember the last active MPRIS player
 pub static LAST_ACTIVE_PLAYER: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
 
+// Last progress read from the active player, whether it was playing, and when it was read
+pub static LAST_PROGRESS: Lazy<Mutex<Option<(TrackProgress, bool, std::time::Instant)>>> = Lazy::new(|| Mutex::new(None));
+
+// Shared session bus connection for MPRIS calls
+static SESSION: tokio::sync::OnceCell<Connection> = tokio::sync::OnceCell::const_new();
+
+// Track of the active player, shown on Now Playing keys
+pub static NOW_PLAYING: Lazy<Mutex<Option<Track>>> = Lazy::new(|| Mutex::new(None));
+
+// Play/Pause keys that show the track title
+pub static PLAY_PAUSE_TITLES: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+// How far long track titles have scrolled
+pub static SCROLL_OFFSET: AtomicUsize = AtomicUsize::new(0);
+
 pub async fn fetch_and_convert_to_data_url(url: &str) -> Result<String> {
-	let bytes = if url.starts_with("data:") {
+	if url.starts_with("data:") {
 		return Ok(url.to_owned());
-	} else if url.starts_with("file:") {
-		let path = url.trim_start_matches("file://");
-		std::fs::read(path)?
-	} else {
-		let response = reqwest::get(url).await?;
-		response.bytes().await?.to_vec()
-	};
+	}
+	let key = cache::cache_key(url);
+	if let Some(data_url) = cache::IMAGE_CACHE.lock().unwrap().get(&key) {
+		return Ok(data_url);
+	}
 
+	let bytes = fetch_image(url).await?;
 	let mime_type = infer::get(&bytes)
 		.map(|info| info.mime_type())
 		.unwrap_or("application/octet-stream");
-	let base64_data = general_purpose::STANDARD.encode(&bytes);
-	Ok(format!("data:{};base64,{}", mime_type, base64_data))
+	let data_url = to_data_url(mime_type, &bytes);
+	cache::IMAGE_CACHE.lock().unwrap().insert(key, data_url.clone());
+	Ok(data_url)
+}
+
+/// Like [`fetch_and_convert_to_data_url`], but scaled down to the device's resolution and marked
+/// with `badge`. Art that can't be decoded is sent as it is.
+pub async fn fetch_album_art(url: &str, badge: Option<art::Badge>) -> Result<String> {
+	let key = format!("{}#{:?}", cache::cache_key(url), badge);
+	if let Some(data_url) = cache::IMAGE_CACHE.lock().unwrap().get(&key) {
+		return Ok(data_url);
+	}
+
+	let bytes = match url.strip_prefix("data:").and_then(|data| data.split_once(";base64,")) {
+		Some((_, base64_data)) => general_purpose::STANDARD.decode(base64_data)?,
+		None => fetch_image(url).await?,
+	};
+	// Decoding and resizing are CPU bound, keep them off the async runtime
+	let options = *art::ART_OPTIONS;
+	let rendered = tokio::task::spawn_blocking(move || art::render_art(&bytes, &options, badge))
+		.await
+		.map_err(anyhow::Error::from)
+		.and_then(|result| result);
+	let data_url = match rendered {
+		Ok((mime_type, rendered)) => to_data_url(mime_type, &rendered),
+		Err(error) => {
+			log::warn!("Failed to resize album art {}: {}", url, error);
+			return fetch_and_convert_to_data_url(url).await;
+		}
+	};
+	cache::IMAGE_CACHE.lock().unwrap().insert(key, data_url.clone());
+	Ok(data_url)
+}
+
+/// Read a local file or download an image, keeping downloads on disk for later runs
+async fn fetch_image(url: &str) -> Result<Vec<u8>> {
+	if let Some(path) = url.strip_prefix("file://") {
+		return Ok(std::fs::read(path)?);
+	}
+	if let Some(bytes) = cache::load_from_disk(url) {
+		return Ok(bytes);
+	}
+	let response = reqwest::get(url).await?.error_for_status()?;
+	let bytes = response.bytes().await?.to_vec();
+	// Error pages served with a success status mustn't end up cached as art
+	if !infer::is_image(&bytes) {
+		anyhow::bail!("{} is not an image", url);
+	}
+	cache::store_on_disk(url, &bytes);
+	Ok(bytes)
+}
+
+fn to_data_url(mime_type: &str, bytes: &[u8]) -> String {
+	format!("data:{};base64,{}", mime_type, general_purpose::STANDARD.encode(bytes))
 }
 
 async fn find_active_player(conn: &Connection) -> Result<String> {
@@ -62,24 +149,25 @@ async fn find_active_player(conn: &Connection) -> Result<String> {
 			player_name.as_str(),
 			"/org/mpris/MediaPlayer2",
 			"org.mpris.MediaPlayer2.Player",
-		).await {
-			if let Ok(status) = player_proxy.get_property::<String>("PlaybackStatus").await {
-				if status == "Playing" {
-					log::info!("Found active player: {} (Playing)", player_name);
-					// Remember this as the last active player
-					*LAST_ACTIVE_PLAYER.lock().unwrap() = Some(player_name.clone());
-					return Ok(player_name.clone());
-				}
+		).await
+			&& let Ok(status) = player_proxy.get_property::<String>("PlaybackStatus").await
+			&& status == "Playing"
+		{
+			// Remember this as the last active player
+			let previous = LAST_ACTIVE_PLAYER.lock().unwrap().replace(player_name.clone());
+			if previous.as_ref() != Some(player_name) {
+				log::info!("Found active player: {} (Playing)", player_name);
 			}
+			return Ok(player_name.clone());
 		}
 	}
 	
 	// If no player is actively playing, try to use the last active one
-	if let Some(last_player) = LAST_ACTIVE_PLAYER.lock().unwrap().clone() {
-		if mpris_players.contains(&last_player) {
-			log::info!("No active player, using last active: {}", last_player);
-			return Ok(last_player);
-		}
+	if let Some(last_player) = LAST_ACTIVE_PLAYER.lock().unwrap().clone()
+		&& mpris_players.contains(&last_player)
+	{
+		log::debug!("No active player, using last active: {}", last_player);
+		return Ok(last_player);
 	}
 	
 	// Fallback to first player if none are actively playing and no last player remembered
@@ -93,11 +181,11 @@ async fn find_active_player(conn: &Connection) -> Result<String> {
 }
 
 async fn get_mpris_proxy() -> Result<Proxy<'static>> {
-	let conn = Connection::session().await?;
-	let player_name = find_active_player(&conn).await?;
+	let conn = SESSION.get_or_try_init(Connection::session).await?;
+	let player_name = find_active_player(conn).await?;
 
 	let proxy = Proxy::new(
-		&conn,
+		conn,
 		player_name,
 		"/org/mpris/MediaPlayer2",
 		"org.mpris.MediaPlayer2.Player",
@@ -113,21 +201,77 @@ async fn call_mpris_method(method: &str) -> Result<()> {
 	Ok(())
 }
 
-async fn get_album_art(metadata: Option<&Value<'_>>) -> Option<String> {
+/// Move the active player's position by `offset` microseconds
+async fn seek_active_player(offset: i64) -> Result<()> {
+	let proxy = get_mpris_proxy().await?;
+	proxy.call_method("Seek", &(offset,)).await?;
+	LAST_PROGRESS.lock().unwrap().take();
+	Ok(())
+}
+
+/// Title, position and length of the active player's track
+async fn get_track_progress() -> Result<TrackProgress> {
+	let proxy = get_mpris_proxy().await?;
+	let metadata: Option<Value> = proxy.get_property("Metadata").await.ok();
+	let progress = TrackProgress {
+		title: get_track(metadata.as_ref()).map(|track| track.title).unwrap_or_default(),
+		// Not every player reports its position
+		position: proxy.get_property("Position").await.ok(),
+		length: get_track_length(metadata.as_ref()),
+	};
+	let playing = proxy.get_property::<String>("PlaybackStatus").await.is_ok_and(|status| status == "Playing");
+	*LAST_PROGRESS.lock().unwrap() = Some((progress.clone(), playing, std::time::Instant::now()));
+	Ok(progress)
+}
+
+/// The last progress read from the player, moved along by the time since if it is playing.
+/// `None` once the reading is older than `max_age`.
+fn estimated_track_progress(max_age: std::time::Duration) -> Option<TrackProgress> {
+	let (progress, playing, read_at) = LAST_PROGRESS.lock().unwrap().clone()?;
+	let elapsed = read_at.elapsed();
+	if elapsed > max_age {
+		return None;
+	}
+	Some(if playing { progress.advanced(elapsed) } else { progress })
+}
+
+async fn get_album_art(metadata: Option<&Value<'_>>, badge: Option<art::Badge>) -> Option<String> {
 	let dict = metadata?.downcast_ref::<zvariant::Dict>().ok()?;
 	let url: String = dict.get(&Value::from("mpris:artUrl")).ok()??;
-	fetch_and_convert_to_data_url(&url).await.ok()
+	fetch_album_art(&url, badge).await.ok()
+}
+
+fn get_track(metadata: Option<&Value<'_>>) -> Option<Track> {
+	let dict = metadata?.downcast_ref::<zvariant::Dict>().ok()?;
+	let title: String = dict.get(&Value::from("xesam:title")).ok()??;
+	// xesam:artist is a list, but some players send a single string
+	let artist = match dict.get::<_, Value>(&Value::from("xesam:artist")).ok().flatten() {
+		Some(Value::Str(artist)) => artist.to_string(),
+		Some(artists) => Vec::<String>::try_from(artists).map(|artists| artists.join(", ")).unwrap_or_default(),
+		None => String::new(),
+	};
+	Some(Track { title, artist })
+}
+
+/// `mpris:length` in microseconds; the spec says signed, but some players send it unsigned
+fn get_track_length(metadata: Option<&Value<'_>>) -> Option<i64> {
+	let dict = metadata?.downcast_ref::<zvariant::Dict>().ok()?;
+	match dict.get::<_, Value>(&Value::from("mpris:length")).ok()?? {
+		Value::I64(length) => Some(length),
+		Value::U64(length) => i64::try_from(length).ok(),
+		_ => None,
+	}
 }
 
 /// Find all MPRIS players for a given process name (e.g., "brave", "firefox")
 async fn find_mpris_players_for_app(app_name: &str) -> Vec<String> {
-	let conn = match Connection::session().await {
+	let conn = match SESSION.get_or_try_init(Connection::session).await {
 		Ok(c) => c,
 		Err(_) => return vec![],
 	};
 	
 	let proxy = match Proxy::new(
-		&conn,
+		conn,
 		"org.freedesktop.DBus",
 		"/org/freedesktop/DBus",
 		"org.freedesktop.DBus",
@@ -149,11 +293,89 @@ async fn find_mpris_players_for_app(app_name: &str) -> Vec<String> {
 		.collect()
 }
 
+/// Every MPRIS player on the session bus with the process ID behind its connection
+async fn mpris_players_with_pids() -> Vec<(String, u32)> {
+	let Ok(conn) = SESSION.get_or_try_init(Connection::session).await else {
+		return vec![];
+	};
+	let Ok(proxy) = Proxy::new(conn, "org.freedesktop.DBus", "/org/freedesktop/DBus", "org.freedesktop.DBus").await else {
+		return vec![];
+	};
+	let names: Vec<String> = proxy.call("ListNames", &()).await.unwrap_or_default();
+
+	let mut players = Vec::new();
+	for name in names.into_iter().filter(|name| name.starts_with("org.mpris.MediaPlayer2.")) {
+		match proxy.call::<_, _, u32>("GetConnectionUnixProcessID", &(name.as_str(),)).await {
+			Ok(pid) => players.push((name, pid)),
+			Err(error) => log::warn!("Failed to get the process of {}: {}", name, error),
+		}
+	}
+	players
+}
+
+/// The parent of a process, from /proc
+fn parent_pid(pid: u32) -> Option<u32> {
+	let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+	// The command name may contain spaces and parentheses, the fields after it don't
+	let (_, fields) = stat.rsplit_once(')')?;
+	fields.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// A process followed by its parent, grandparent and so on up to init
+fn process_ancestry(pid: u32) -> Vec<u32> {
+	let mut ancestry = vec![pid];
+	while let Some(parent) = parent_pid(*ancestry.last().unwrap()) {
+		if parent <= 1 || ancestry.contains(&parent) || ancestry.len() >= 32 {
+			break;
+		}
+		ancestry.push(parent);
+	}
+	ancestry
+}
+
+/// The closest process in `ancestry` that owns a player. Browsers play audio from a child process
+/// but register MPRIS in the main one.
+fn player_process(players: &[(String, u32)], ancestry: &[u32]) -> Option<u32> {
+	ancestry.iter().copied().find(|pid| players.iter().any(|(_, player_pid)| player_pid == pid))
+}
+
+/// The players belonging to the closest process in `ancestry` that has any, sorted by name
+fn players_for_process(players: &[(String, u32)], ancestry: &[u32]) -> Vec<String> {
+	let Some(pid) = player_process(players, ancestry) else {
+		return vec![];
+	};
+	let mut matched: Vec<String> = players
+		.iter()
+		.filter(|(_, player_pid)| *player_pid == pid)
+		.map(|(name, _)| name.clone())
+		.collect();
+	matched.sort();
+	matched
+}
+
+/// The process ID a stream was opened by
+fn stream_pid(sink_input: &SinkInput) -> Option<u32> {
+	sink_input.property("application.process.id")?.parse().ok()
+}
+
+/// The streams whose players live in process `owner`, sorted by index
+fn streams_of_player_process(sink_inputs: &[SinkInput], players: &[(String, u32)], owner: u32) -> Vec<u32> {
+	let mut streams: Vec<u32> = sink_inputs
+		.iter()
+		.filter(|sink_input| {
+			stream_pid(sink_input).is_some_and(|pid| player_process(players, &process_ancestry(pid)) == Some(owner))
+		})
+		.map(|sink_input| sink_input.index)
+		.collect();
+	streams.sort();
+	streams
+}
+
 /// Try to get album art from a specific MPRIS player instance
 async fn get_album_art_from_player(player_name: &str) -> Option<String> {
-	let conn = Connection::session().await.ok()?;
+	let conn = SESSION.get_or_try_init(Connection::session).await.ok()?;
 	let proxy = Proxy::new(
-		&conn,
+		conn,
 		player_name,
 		"/org/mpris/MediaPlayer2",
 		"org.mpris.MediaPlayer2.Player",
@@ -161,53 +383,27 @@ async fn get_album_art_from_player(player_name: &str) -> Option<String> {
 	.await.ok()?;
 	
 	let metadata = proxy.get_property("Metadata").await.ok()?;
-	get_album_art(Some(&metadata)).await
+	get_album_art(Some(&metadata), None).await
 }
 
 /// Get album art for a specific sink input by matching it with the corresponding MPRIS instance
-/// When there are multiple tabs/sources from the same app, this tries to match them by index
+/// The stream's process (or the closest ancestor with a player) decides first; when there are
+/// multiple tabs/sources from the same app, this tries to match them by index
 /// process_binary: the actual binary name from pactl (e.g., "chrome")
 /// mpris_name: optional override for MPRIS lookup (e.g., "chromium" for chrome)
-pub async fn get_album_art_for_sink_input(sink_input_id: usize, process_binary: &str, mpris_name: Option<&str>) -> Option<String> {
-	// Get full sink input list once
-	let info_output = std::process::Command::new("pactl")
-		.args(&["list", "sink-inputs"])
-		.output()
-		.ok()?;
-	
-	let info = String::from_utf8_lossy(&info_output.stdout);
-	
-	// Parse all sink inputs and filter by process binary
-	let mut sink_inputs: Vec<usize> = Vec::new();
-	let mut current_id: Option<usize> = None;
-	let mut in_matching_app = false;
-	
-	for line in info.lines() {
-		if line.starts_with("Sink Input #") {
-			// Save previous entry if it matches
-			if in_matching_app {
-				if let Some(id) = current_id {
-					sink_inputs.push(id);
-				}
-			}
-			// Reset for new entry
-			current_id = line.trim_start_matches("Sink Input #").parse().ok();
-			in_matching_app = false;
-		} else if line.contains("application.process.binary") {
-			if let Some(binary) = line.split('"').nth(1) {
-				if binary == process_binary {
-					in_matching_app = true;
-				}
-			}
-		}
-	}
-	
-	// Don't forget the last entry
-	if in_matching_app {
-		if let Some(id) = current_id {
-			sink_inputs.push(id);
-		}
-	}
+pub async fn get_album_art_for_sink_input(sink_input_id: u32, process_binary: &str, mpris_name: Option<&str>) -> Option<String> {
+	let all_sink_inputs = AUDIO.sink_inputs().await.ok()?;
+	let pid = all_sink_inputs
+		.iter()
+		.find(|sink_input| sink_input.index == sink_input_id)
+		.and_then(stream_pid);
+
+	// Get all sink inputs belonging to this process binary
+	let mut sink_inputs: Vec<u32> = all_sink_inputs
+		.iter()
+		.filter(|sink_input| sink_input.process_binary() == process_binary)
+		.map(|sink_input| sink_input.index)
+		.collect();
 	
 	sink_inputs.sort(); // Sort to get consistent ordering
 	
@@ -217,6 +413,38 @@ pub async fn get_album_art_for_sink_input(sink_input_id: usize, process_binary:
 	log::info!("Sink input {} is at index {} among {} total sink inputs for {} (IDs: {:?})", 
 		sink_input_id, sink_index, sink_inputs.len(), process_binary, sink_inputs);
 	
+	let players = match pid {
+		Some(_) => mpris_players_with_pids().await,
+		None => vec![],
+	};
+	if let Some(pid) = pid
+		&& let Some(owner) = player_process(&players, &process_ancestry(pid))
+	{
+		let matched = players_for_process(&players, &[owner]);
+		log::info!("MPRIS players for process {} of sink input {}: {:?}", owner, sink_input_id, matched);
+		// Several players in one process (e.g. browser tabs) are told apart by the stream's position
+		// among that process's streams
+		let player = match matched.len() {
+			1 => matched.first(),
+			_ => streams_of_player_process(&all_sink_inputs, &players, owner)
+				.iter()
+				.position(|&id| id == sink_input_id)
+				.and_then(|index| matched.get(index)),
+		};
+		if let Some(player) = player
+			&& let Some(album_art) = get_album_art_from_player(player).await
+		{
+			log::info!("Got album art from {} by process", player);
+			return Some(album_art);
+		}
+		for player in &matched {
+			if let Some(album_art) = get_album_art_from_player(player).await {
+				log::info!("Got album art from {} by process", player);
+				return Some(album_art);
+			}
+		}
+	}
+	
 	// Get all MPRIS instances for this app, sorted
 	// Use mpris_name override if provided (e.g., "chromium" for "chrome")
 	let mpris_lookup_name = mpris_name.unwrap_or(process_binary);
@@ -272,26 +500,91 @@ pub async fn get_album_art_for_sink_input(sink_input_id: usize, process_binary:
 	None
 }
 
-async fn update_play_pause(instance: &Instance, image: Option<String>) -> OpenActionResult<()> {
+/// Show the playback state on the Play/Pause key: the manifest's play or pause image without art,
+/// and a badge drawn onto the art otherwise
+async fn update_play_pause(instance: &Instance, image: Option<String>, status: Option<art::Badge>) -> OpenActionResult<()> {
+	let state = match status {
+		Some(art::Badge::Playing) => 1,
+		Some(art::Badge::Paused) => 2,
+		Some(art::Badge::Stopped) | None => 0,
+	};
+	instance.set_state(state).await?;
 	instance.set_image(image, None).await
 }
 
 async fn update_all() {
+	// The track or playback state changed, so seek dials read the position again
+	LAST_PROGRESS.lock().unwrap().take();
 	let proxy_result = get_mpris_proxy().await;
 	let get_property = async |property: &str| match &proxy_result {
 		Ok(proxy) => proxy.get_property(property).await.ok(),
 		Err(_) => None,
 	};
+	let status = match &proxy_result {
+		Ok(proxy) => proxy
+			.get_property::<String>("PlaybackStatus")
+			.await
+			.ok()
+			.and_then(|status| art::Badge::from_status(&status)),
+		Err(_) => None,
+	};
+	let badge = status.filter(|_| art::ART_OPTIONS.badge);
+	let metadata = get_property("Metadata").await;
+	let album_art = get_album_art(metadata.as_ref(), badge).await;
 	for instance in visible_instances(PlayPauseAction::UUID).await {
-		if let Err(error) = update_play_pause(
-			&instance,
-			get_album_art(get_property("Metadata").await.as_ref()).await,
-		)
-		.await
-		{
+		if let Err(error) = update_play_pause(&instance, album_art.clone(), status).await {
 			log::error!("Failed to update PlayPause: {}", error);
 		}
 	}
+
+	let track = get_track(metadata.as_ref());
+	{
+		let mut now_playing = NOW_PLAYING.lock().unwrap();
+		if *now_playing != track {
+			// Start a new track's title from the beginning
+			*now_playing = track;
+			SCROLL_OFFSET.store(0, Ordering::Relaxed);
+		}
+	}
+	update_track_titles().await;
+}
+
+/// How long seek dials count the position up before reading it from the player again
+const SEEK_RESYNC_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Keep the position on seek dials current; players only signal jumps, not playback moving on
+async fn watch_seek_dials() {
+	let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
+	loop {
+		interval.tick().await;
+		let instances = visible_instances(SeekDialAction::UUID).await;
+		if instances.is_empty() {
+			continue;
+		}
+		// Count the position up locally and only ask the player again every few seconds, or after
+		// a track or playback change cleared the last reading
+		let progress = match estimated_track_progress(SEEK_RESYNC_INTERVAL) {
+			Some(progress) => progress,
+			None => get_track_progress().await.unwrap_or_default(),
+		};
+		for instance in instances {
+			if let Err(error) = instance.set_feedback(&progress.feedback()).await {
+				log::error!("Failed to update seek dial: {}", error);
+			}
+		}
+	}
+}
+
+/// Move long track titles along
+async fn scroll_track_titles() {
+	let mut interval = tokio::time::interval(SCROLL_INTERVAL);
+	loop {
+		interval.tick().await;
+		if NOW_PLAYING.lock().unwrap().as_ref().is_some_and(Track::scrolls) {
+			SCROLL_OFFSET.fetch_add(1, Ordering::Relaxed);
+			update_track_titles().await;
+		}
+	}
 }
 
 async fn watch_album_art() {
@@ -386,21 +679,10 @@ async fn watch_album_art() {
 				continue;
 			}
 
-			if let Some(playback_status_value) = changed_properties.get("PlaybackStatus") {
-				if let Ok(status_str) = playback_status_value.downcast_ref::<zvariant::Str>() {
-					if status_str.as_str() == "Stopped" {
-						update_all().await;
-						continue;
-					}
-				}
-			}
-
-			let album_art_url = get_album_art(changed_properties.get("Metadata")).await;
-
-			for instance in visible_instances(PlayPauseAction::UUID).await {
-				if let Err(error) = update_play_pause(&instance, album_art_url.clone()).await {
-					log::error!("Failed to update PlayPause: {}", error);
-				}
+			// The key shows the active player's art and state together, so re-read both; the art
+			// itself comes from the cache
+			if changed_properties.contains_key("PlaybackStatus") || changed_properties.contains_key("Metadata") {
+				update_all().await;
 			}
 			for instance in visible_instances(VolumeDialAction::UUID).await {
 				log::info!("Updating dial image for instance {:?}", instance.instance_id);
@@ -412,6 +694,210 @@ async fn watch_album_art() {
 	}
 }
 
+/// Refresh every visible volume dial whose target matches. Repainting the image also refreshes
+/// the feedback; without `image` only the name, level and mute indicator are updated.
+async fn update_dials(matches: impl Fn(&DialTarget) -> bool, image: bool) {
+	for instance in visible_instances(VolumeDialAction::UUID).await {
+		if !matches(&dial_target(&instance.instance_id)) {
+			continue;
+		}
+		let result = if image {
+			update_dial_image_for_selected_sink(&instance).await
+		} else {
+			update_dial_feedback(&instance).await
+		};
+		result.unwrap_or_else(|e| {
+			log::error!("Failed to update dial: {}", e);
+		});
+	}
+}
+
+/// Refresh every visible volume dial bound to a stream, or to its whole app
+async fn update_dials_for_stream(sink_input: &SinkInput, image: bool) {
+	let app_id = sink_input.app_id();
+	update_dials(
+		|target| match target {
+			DialTarget::App(id) => *id == app_id,
+			DialTarget::Stream(index) => *index == sink_input.index,
+			_ => false,
+		},
+		image,
+	)
+	.await;
+}
+
+/// Refresh every visible routing dial showing `app_id`
+async fn update_route_dials(app_id: &str) {
+	for instance in visible_instances(AppRouteAction::UUID).await {
+		if matches!(dial_target(&instance.instance_id), DialTarget::App(id) if id == app_id) {
+			update_route_dial(&instance).await.unwrap_or_else(|e| {
+				log::error!("Failed to update routing dial: {}", e);
+			});
+		}
+	}
+}
+
+/// Refresh every visible volume dial bound to master volume or a device
+async fn update_device_dials(image: bool) {
+	update_dials(|target| !matches!(target, DialTarget::App(_) | DialTarget::Stream(_)), image).await;
+}
+
+/// Reload the whole registry, e.g. after (re)connecting to the sound server
+async fn refresh_sink_inputs() {
+	match AUDIO.sink_inputs().await {
+		Ok(sink_inputs) => {
+			let mut registry = SINK_INPUTS.lock().unwrap();
+			*registry = sink_inputs.into_iter().map(|sink_input| (sink_input.index, sink_input)).collect();
+			log::info!("Tracking {} sink inputs", registry.len());
+		}
+		Err(error) => log::error!("Failed to list sink inputs: {}", error),
+	}
+}
+
+async fn handle_sink_input_event(kind: EventKind, index: u32) {
+	if kind == EventKind::Remove {
+		let Some(removed) = SINK_INPUTS.lock().unwrap().remove(&index) else {
+			return;
+		};
+		let app_id = removed.app_id();
+		log::info!("Sink input {} ({}) went away", index, app_id);
+
+		// Dials stay bound to an app between its streams (Discord between calls, a paused browser
+		// tab) and show it as unavailable until it plays again
+		// Stream indices are never reused, so a dial bound to just this stream goes back to master
+		let mut released = Vec::new();
+		for (instance_id, target) in DIAL_STATES.lock().unwrap().iter_mut() {
+			if *target == DialTarget::Stream(index) {
+				*target = DialTarget::Master;
+				released.push(instance_id.clone());
+			}
+		}
+		for instance_id in released {
+			if let Some(instance) = get_instance(instance_id).await {
+				save_dial_target(&instance, &DialTarget::Master).await.unwrap_or_else(|e| {
+					log::error!("Failed to save dial target: {}", e);
+				});
+				update_dial_image_for_selected_sink(&instance).await.unwrap_or_else(|e| {
+					log::error!("Failed to update dial: {}", e);
+				});
+			}
+		}
+		update_dials_for_stream(&removed, true).await;
+		update_route_dials(&app_id).await;
+		return;
+	}
+
+	let sink_input = match AUDIO.sink_input(index).await {
+		Ok(sink_input) => sink_input,
+		// Already gone again; the remove event will follow
+		Err(_) => return,
+	};
+	let previous = SINK_INPUTS.lock().unwrap().insert(index, sink_input.clone());
+	if kind == EventKind::New {
+		log::info!("New sink input {}: {} [{}]", index, sink_input.app_name(), sink_input.app_id());
+		update_dials_for_stream(&sink_input, true).await;
+		update_route_dials(&sink_input.app_id()).await;
+		return;
+	}
+
+	if previous.as_ref().is_none_or(|previous| previous.sink != sink_input.sink) {
+		// Moved to another output
+		update_route_dials(&sink_input.app_id()).await;
+	}
+	if previous
+		.as_ref()
+		.is_none_or(|previous| previous.properties != sink_input.properties || previous.muted != sink_input.muted)
+	{
+		// New metadata and muting change the image
+		update_dials_for_stream(&sink_input, true).await;
+	} else if previous.is_some_and(|previous| previous.volume != sink_input.volume) {
+		// A volume change only moves the level, e.g. from another mixer
+		update_dials_for_stream(&sink_input, false).await;
+	}
+}
+
+/// Refresh every microphone dial and key; the dial image only if `image` is set
+async fn update_mic_actions(image: bool) {
+	for instance in visible_instances(MicDialAction::UUID).await {
+		let result = if image { update_mic_dial(&instance).await } else { update_mic_feedback(&instance).await };
+		result.unwrap_or_else(|e| {
+			log::error!("Failed to update microphone dial: {}", e);
+		});
+	}
+	if image {
+		for instance in visible_instances(MicMuteAction::UUID).await {
+			update_mic_mute(&instance).await.unwrap_or_else(|e| {
+				log::error!("Failed to update microphone key: {}", e);
+			});
+		}
+	}
+}
+
+async fn update_output_switches() {
+	for instance in visible_instances(OutputSwitchAction::UUID).await {
+		update_output_switch(&instance).await.unwrap_or_else(|e| {
+			log::error!("Failed to update output switch: {}", e);
+		});
+	}
+}
+
+/// A sink or source changed; repaint device dials if anything was muted or unmuted,
+/// and refresh their level if a volume changed
+async fn handle_device_event() {
+	let devices = match (AUDIO.sinks().await, AUDIO.sources().await) {
+		(Ok(sinks), Ok(sources)) => sinks.into_iter().chain(sources),
+		(Err(error), _) | (_, Err(error)) => {
+			log::error!("Failed to list devices: {}", error);
+			return;
+		}
+	};
+	let states: HashMap<String, (bool, u32)> = devices
+		.map(|device| (device.name, (device.muted, volume_percent(&device.volume))))
+		.collect();
+	let previous = std::mem::replace(&mut *DEVICE_STATES.lock().unwrap(), states.clone());
+	let mute_changed = states.len() != previous.len()
+		|| states.iter().any(|(name, (muted, _))| previous.get(name).is_none_or(|(was_muted, _)| was_muted != muted));
+	if mute_changed {
+		update_device_dials(true).await;
+		update_mic_actions(true).await;
+	} else if states != previous {
+		update_device_dials(false).await;
+		update_mic_actions(false).await;
+	}
+}
+
+async fn watch_sink_inputs() {
+	loop {
+		let mut events = match AUDIO.subscribe().await {
+			Ok(events) => events,
+			Err(error) => {
+				log::error!("Failed to subscribe to sound server events: {}", error);
+				tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+				continue;
+			}
+		};
+
+		refresh_sink_inputs().await;
+
+		while let Some(event) = events.recv().await {
+			match event.facility {
+				Facility::SinkInput => handle_sink_input_event(event.kind, event.index).await,
+				Facility::Sink | Facility::Source => handle_device_event().await,
+				// The default sink may have changed
+				Facility::Server => {
+					update_device_dials(true).await;
+					update_mic_actions(true).await;
+					update_output_switches().await;
+				}
+				_ => {}
+			}
+		}
+
+		log::warn!("Lost sound server event stream, resubscribing");
+		tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+	}
+}
+
 #[tokio::main]
 async fn main() -> OpenActionResult<()> {
 	simplelog::TermLogger::init(
@@ -425,13 +911,70 @@ async fn main() -> OpenActionResult<()> {
 	// log::info!("Args: {:?}", std::env::args().collect::<Vec<_>>());
 
 	register_action(PlayPauseAction {}).await;
+	register_action(NowPlayingAction {}).await;
 	register_action(StopAction {}).await;
 	register_action(PreviousAction {}).await;
 	register_action(NextAction {}).await;
 	register_action(VolumeDialAction {}).await;
+	register_action(OutputSwitchAction {}).await;
+	register_action(AppRouteAction {}).await;
+	register_action(MicDialAction {}).await;
+	register_action(MicMuteAction {}).await;
+	register_action(SeekDialAction {}).await;
 	register_action(DialTestAction {}).await;
 
 	tokio::spawn(watch_album_art());
+	tokio::spawn(watch_sink_inputs());
+	tokio::spawn(scroll_track_titles());
+	tokio::spawn(watch_seek_dials());
 
 	run(std::env::args().collect()).await
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn players(entries: &[(&str, u32)]) -> Vec<(String, u32)> {
+		entries.iter().map(|&(name, pid)| (name.to_owned(), pid)).collect()
+	}
+
+	#[test]
+	fn players_match_the_closest_process() {
+		let players = players(&[
+			("org.mpris.MediaPlayer2.firefox.instance_1_20", 1000),
+			("org.mpris.MediaPlayer2.spotify", 2000),
+			("org.mpris.MediaPlayer2.firefox.instance_1_10", 1000),
+		]);
+
+		assert_eq!(players_for_process(&players, &[2000]), vec!["org.mpris.MediaPlayer2.spotify"]);
+		// A content process playing for the browser that owns the players
+		assert_eq!(
+			players_for_process(&players, &[1234, 1000, 900]),
+			vec!["org.mpris.MediaPlayer2.firefox.instance_1_10", "org.mpris.MediaPlayer2.firefox.instance_1_20"]
+		);
+		assert!(players_for_process(&players, &[3000, 900]).is_empty());
+		assert_eq!(player_process(&players, &[1234, 1000, 900]), Some(1000));
+	}
+
+	#[test]
+	fn streams_are_counted_per_player_process() {
+		let own = std::process::id();
+		let stream = |index: u32, pid: u32| SinkInput {
+			index,
+			properties: HashMap::from([("application.process.id".to_owned(), pid.to_string())]),
+			..Default::default()
+		};
+		let sink_inputs = vec![stream(30, own), stream(12, u32::MAX), stream(20, own), SinkInput { index: 5, ..Default::default() }];
+		let players = players(&[("org.mpris.MediaPlayer2.firefox.instance_1_10", own)]);
+
+		assert_eq!(streams_of_player_process(&sink_inputs, &players, own), vec![20, 30]);
+	}
+
+	#[test]
+	fn ancestry_starts_with_the_process_itself() {
+		let ancestry = process_ancestry(std::process::id());
+		assert_eq!(ancestry[0], std::process::id());
+		assert_eq!(ancestry.get(1).copied(), parent_pid(std::process::id()).filter(|&parent| parent > 1));
+	}
+}
@@ -0,0 +1,406 @@
+//! Audio backend that shells out to `pactl` and `wpctl`, as the plugin originally did
+
+use super::audio::{
+	AudioBackend, AudioEvent, DEFAULT_SINK, Device, EventKind, Facility, ServerInfo, SinkInput, adjust_volume,
+	volume_percent,
+};
+
+use anyhow::{Result, anyhow, bail};
+use openaction::async_trait;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+
+async fn run(program: &str, args: &[&str]) -> Result<String> {
+	// Section headers and field names are translated, so always ask for untranslated output
+	let output = tokio::process::Command::new(program)
+		.args(args)
+		.env("LC_ALL", "C")
+		.output()
+		.await?;
+	if !output.status.success() {
+		bail!(
+			"{} {} failed: {}",
+			program,
+			args.join(" "),
+			String::from_utf8_lossy(&output.stderr).trim()
+		);
+	}
+	Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+async fn pactl(args: &[&str]) -> Result<String> {
+	run("pactl", args).await
+}
+
+/// One `<Kind> #<index>` block of `pactl list` output
+#[derive(Debug, Default)]
+struct Section {
+	index: u32,
+	/// Top-level `Key: value` lines
+	fields: HashMap<String, String>,
+	/// Nested `key = "value"` lines from the properties list
+	properties: HashMap<String, String>,
+}
+
+impl Section {
+	fn field(&self, key: &str) -> Option<&str> {
+		self.fields.get(key).map(String::as_str)
+	}
+
+	fn flag(&self, key: &str) -> bool {
+		self.field(key) == Some("yes")
+	}
+
+	fn index_field(&self, key: &str) -> Option<u32> {
+		self.field(key)?.parse().ok()
+	}
+}
+
+/// Parse a `key = "value"` property line, undoing pactl's quote escaping
+fn parse_property(line: &str) -> Option<(String, String)> {
+	let (key, value) = line.split_once(" = ")?;
+	let value = value.strip_prefix('"')?.strip_suffix('"')?;
+	let mut unescaped = String::with_capacity(value.len());
+	let mut chars = value.chars();
+	while let Some(c) = chars.next() {
+		if c == '\\' {
+			unescaped.extend(chars.next());
+		} else {
+			unescaped.push(c);
+		}
+	}
+	Some((key.trim().to_owned(), unescaped))
+}
+
+/// Split `pactl list <kind>` output into sections. Every section is parsed on its own,
+/// so a field missing from one entry is never picked up from the next.
+fn parse_sections(output: &str) -> Vec<Section> {
+	let mut sections = Vec::new();
+	let mut current: Option<Section> = None;
+
+	for line in output.lines() {
+		if line.trim().is_empty() {
+			continue;
+		}
+
+		if !line.starts_with(char::is_whitespace) {
+			// Section header such as "Sink Input #48"; the index is always after the '#'
+			sections.extend(current.take());
+			current = line
+				.rsplit_once('#')
+				.and_then(|(_, index)| index.trim().parse().ok())
+				.map(|index| Section { index, ..Default::default() });
+			continue;
+		}
+
+		let Some(section) = current.as_mut() else {
+			continue;
+		};
+		let depth = line.chars().take_while(|&c| c == '\t').count();
+		let content = line.trim();
+
+		if depth >= 2 {
+			if let Some((key, value)) = parse_property(content) {
+				section.properties.insert(key, value);
+			}
+		} else if !line[depth..].starts_with(' ')
+			&& let Some((key, value)) = content.split_once(':')
+		{
+			// Lines indented with extra spaces continue the previous field (e.g. "balance 0.00")
+			section.fields.insert(key.trim().to_owned(), value.trim().to_owned());
+		}
+	}
+	sections.extend(current);
+	sections
+}
+
+/// Raw channel values from a `front-left: 65536 / 100% / 0.00 dB, ...` volume field
+fn parse_volume(value: &str) -> Vec<u32> {
+	let tokens: Vec<&str> = value.split_whitespace().collect();
+	tokens
+		.windows(3)
+		.filter(|window| window[0].ends_with(':') && window[2] == "/")
+		.filter_map(|window| window[1].parse().ok())
+		.collect()
+}
+
+/// Parse the output of `pactl list sink-inputs`
+pub fn parse_sink_inputs(output: &str) -> Vec<SinkInput> {
+	parse_sections(output)
+		.into_iter()
+		.map(|section| SinkInput {
+			index: section.index,
+			name: section.properties.get("media.name").cloned(),
+			client: section.index_field("Client"),
+			sink: section.index_field("Sink").unwrap_or_default(),
+			volume: section.field("Volume").map(parse_volume).unwrap_or_default(),
+			muted: section.flag("Mute"),
+			corked: section.flag("Corked"),
+			properties: section.properties,
+		})
+		.collect()
+}
+
+/// Parse the output of `pactl list sinks` or `pactl list sources`
+pub fn parse_devices(output: &str) -> Vec<Device> {
+	parse_sections(output)
+		.into_iter()
+		.map(|section| Device {
+			index: section.index,
+			name: section.field("Name").unwrap_or_default().to_owned(),
+			description: section.field("Description").map(str::to_owned),
+			volume: section.field("Volume").map(parse_volume).unwrap_or_default(),
+			muted: section.flag("Mute"),
+			properties: section.properties,
+		})
+		.collect()
+}
+
+/// Parse a `pactl subscribe` line such as `Event 'remove' on sink-input #61`
+fn parse_event(line: &str) -> Option<AudioEvent> {
+	let mut words = line.split_whitespace();
+	if words.next()? != "Event" {
+		return None;
+	}
+	let kind = match words.next()?.trim_matches('\'') {
+		"new" => EventKind::New,
+		"change" => EventKind::Change,
+		"remove" => EventKind::Remove,
+		_ => return None,
+	};
+	if words.next()? != "on" {
+		return None;
+	}
+	let facility = match words.next()? {
+		"sink" => Facility::Sink,
+		"source" => Facility::Source,
+		"sink-input" => Facility::SinkInput,
+		"source-output" => Facility::SourceOutput,
+		"server" => Facility::Server,
+		_ => Facility::Other,
+	};
+	let index = words
+		.next()
+		.and_then(|word| word.strip_prefix('#')?.parse().ok())
+		.unwrap_or_default();
+	Some(AudioEvent { facility, kind, index })
+}
+
+fn bool_arg(value: bool) -> &'static str {
+	if value { "1" } else { "0" }
+}
+
+/// Backend built on the `pactl`/`wpctl` command line tools
+pub struct CommandBackend;
+
+#[async_trait]
+impl AudioBackend for CommandBackend {
+	async fn server_info(&self) -> Result<ServerInfo> {
+		let default_sink = pactl(&["get-default-sink"]).await?.trim().to_owned();
+		let default_source = pactl(&["get-default-source"]).await?.trim().to_owned();
+		Ok(ServerInfo {
+			default_sink: Some(default_sink).filter(|name| !name.is_empty()),
+			default_source: Some(default_source).filter(|name| !name.is_empty()),
+		})
+	}
+
+	async fn sink_inputs(&self) -> Result<Vec<SinkInput>> {
+		Ok(parse_sink_inputs(&pactl(&["list", "sink-inputs"]).await?))
+	}
+
+	async fn set_sink_input_volume(&self, index: u32, volume: &[u32]) -> Result<()> {
+		let index = index.to_string();
+		let volume: Vec<String> = volume.iter().map(u32::to_string).collect();
+		let mut args = vec!["set-sink-input-volume", index.as_str()];
+		args.extend(volume.iter().map(String::as_str));
+		pactl(&args).await?;
+		Ok(())
+	}
+
+	async fn set_sink_input_mute(&self, index: u32, muted: bool) -> Result<()> {
+		pactl(&["set-sink-input-mute", &index.to_string(), bool_arg(muted)]).await?;
+		Ok(())
+	}
+
+	async fn move_sink_input(&self, index: u32, sink: &str) -> Result<()> {
+		pactl(&["move-sink-input", &index.to_string(), sink]).await?;
+		Ok(())
+	}
+
+	async fn sinks(&self) -> Result<Vec<Device>> {
+		Ok(parse_devices(&pactl(&["list", "sinks"]).await?))
+	}
+
+	async fn sources(&self) -> Result<Vec<Device>> {
+		Ok(parse_devices(&pactl(&["list", "sources"]).await?))
+	}
+
+	async fn set_sink_volume(&self, name: &str, volume: &[u32]) -> Result<()> {
+		let volume: Vec<String> = volume.iter().map(u32::to_string).collect();
+		let mut args = vec!["set-sink-volume", name];
+		args.extend(volume.iter().map(String::as_str));
+		pactl(&args).await?;
+		Ok(())
+	}
+
+	async fn set_sink_mute(&self, name: &str, muted: bool) -> Result<()> {
+		pactl(&["set-sink-mute", name, bool_arg(muted)]).await?;
+		Ok(())
+	}
+
+	async fn set_default_sink(&self, name: &str) -> Result<()> {
+		pactl(&["set-default-sink", name]).await?;
+		Ok(())
+	}
+
+	async fn set_source_volume(&self, name: &str, volume: &[u32]) -> Result<()> {
+		let volume: Vec<String> = volume.iter().map(u32::to_string).collect();
+		let mut args = vec!["set-source-volume", name];
+		args.extend(volume.iter().map(String::as_str));
+		pactl(&args).await?;
+		Ok(())
+	}
+
+	async fn set_source_mute(&self, name: &str, muted: bool) -> Result<()> {
+		pactl(&["set-source-mute", name, bool_arg(muted)]).await?;
+		Ok(())
+	}
+
+	async fn subscribe(&self) -> Result<UnboundedReceiver<AudioEvent>> {
+		let mut child = std::process::Command::new("pactl")
+			.arg("subscribe")
+			.env("LC_ALL", "C")
+			.stdout(std::process::Stdio::piped())
+			.spawn()?;
+		let stdout = child
+			.stdout
+			.take()
+			.ok_or_else(|| anyhow!("pactl subscribe has no output"))?;
+
+		let (sender, receiver) = unbounded_channel();
+		std::thread::spawn(move || {
+			for line in BufReader::new(stdout).lines().map_while(|line| line.ok()) {
+				if let Some(event) = parse_event(&line)
+					&& sender.send(event).is_err()
+				{
+					break;
+				}
+			}
+			let _ = child.kill();
+			let _ = child.wait();
+		});
+		Ok(receiver)
+	}
+
+	async fn change_sink_input_volume(&self, index: u32, delta_percent: i32, limit_percent: Option<u32>) -> Result<u32> {
+		if limit_percent.is_some() {
+			// pactl can't clamp relative changes
+			let sink_input = self.sink_input(index).await?;
+			let volume = adjust_volume(&sink_input.volume, delta_percent, limit_percent);
+			self.set_sink_input_volume(index, &volume).await?;
+			return Ok(volume_percent(&volume));
+		}
+
+		// pactl uses +/- prefix format
+		pactl(&["set-sink-input-volume", &index.to_string(), &format!("{:+}%", delta_percent)]).await?;
+		Ok(volume_percent(&self.sink_input(index).await?.volume))
+	}
+
+	async fn change_sink_volume(&self, name: &str, delta_percent: i32, limit_percent: Option<u32>) -> Result<u32> {
+		if name != DEFAULT_SINK {
+			let sink = self.sink(name).await?;
+			let volume = adjust_volume(&sink.volume, delta_percent, limit_percent);
+			self.set_sink_volume(name, &volume).await?;
+			return Ok(volume_percent(&volume));
+		}
+
+		// wpctl uses a % suffix and can clamp for us
+		let change = format!("{}%{}", delta_percent.abs(), if delta_percent < 0 { "-" } else { "+" });
+		let limit = limit_percent.map(|percent| format!("{:.2}", percent as f32 / 100.0));
+		let mut args = vec!["set-volume", "@DEFAULT_AUDIO_SINK@", change.as_str()];
+		if let Some(limit) = &limit {
+			args.extend(["--limit", limit.as_str()]);
+		}
+		run("wpctl", &args).await?;
+		Ok(volume_percent(&self.sink(DEFAULT_SINK).await?.volume))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_every_sink_input_field() {
+		let sink_inputs = parse_sink_inputs(include_str!("../tests/fixtures/pactl-list-sink-inputs.txt"));
+		assert_eq!(sink_inputs.len(), 3);
+
+		let firefox = &sink_inputs[0];
+		assert_eq!(firefox.index, 48);
+		assert_eq!(firefox.client, Some(47));
+		assert_eq!(firefox.sink, 55);
+		assert_eq!(firefox.volume, vec![52429, 52429]);
+		assert!(!firefox.muted);
+		assert!(!firefox.corked);
+		assert_eq!(firefox.app_name(), "Firefox");
+		assert_eq!(firefox.process_binary(), "firefox");
+		assert_eq!(firefox.property("application.process.id"), Some("4242"));
+		assert_eq!(firefox.name.as_deref(), Some("AudioStream"));
+
+		let chrome = &sink_inputs[1];
+		assert!(chrome.corked);
+		assert_eq!(chrome.name.as_deref(), Some(r#"Playback "Live" \ Radio"#));
+	}
+
+	#[test]
+	fn missing_fields_do_not_leak_from_the_next_entry() {
+		let sink_inputs = parse_sink_inputs(include_str!("../tests/fixtures/pactl-list-sink-inputs.txt"));
+
+		let notification = &sink_inputs[2];
+		assert_eq!(notification.index, 73);
+		assert_eq!(notification.client, None);
+		assert_eq!(notification.sink, 56);
+		assert_eq!(notification.volume, vec![32768]);
+		assert!(notification.muted);
+		assert_eq!(notification.process_binary(), "");
+		assert_eq!(notification.app_name(), "notify-send");
+	}
+
+	#[test]
+	fn localized_output_still_splits_entries_and_properties() {
+		let sink_inputs = parse_sink_inputs(include_str!("../tests/fixtures/pactl-list-sink-inputs.de.txt"));
+
+		assert_eq!(sink_inputs.len(), 2);
+		assert_eq!(sink_inputs[0].index, 48);
+		assert_eq!(sink_inputs[0].process_binary(), "firefox");
+		assert_eq!(sink_inputs[1].index, 73);
+		assert_eq!(sink_inputs[1].process_binary(), "");
+	}
+
+	#[test]
+	fn parses_subscribe_events() {
+		assert_eq!(
+			parse_event("Event 'remove' on sink-input #61"),
+			Some(AudioEvent { facility: Facility::SinkInput, kind: EventKind::Remove, index: 61 })
+		);
+		assert_eq!(
+			parse_event("Event 'change' on sink #55"),
+			Some(AudioEvent { facility: Facility::Sink, kind: EventKind::Change, index: 55 })
+		);
+		assert_eq!(parse_event("Connection failure: Connection refused"), None);
+	}
+
+	#[test]
+	fn parses_sinks() {
+		let sinks = parse_devices(include_str!("../tests/fixtures/pactl-list-sinks.txt"));
+
+		assert_eq!(sinks.len(), 2);
+		assert_eq!(sinks[0].name, "alsa_output.pci-0000_0c_00.4.analog-stereo");
+		assert_eq!(sinks[0].volume, vec![45875, 45875]);
+		assert!(!sinks[0].muted);
+		assert_eq!(sinks[1].description.as_deref(), Some("WH-1000XM4"));
+		assert!(sinks[1].muted);
+		assert_eq!(sinks[1].properties.get("device.icon_name").map(String::as_str), Some("audio-headphones-bluetooth"));
+	}
+}
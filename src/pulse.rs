@@ -0,0 +1,782 @@
+//! Minimal in-process client for the PulseAudio native protocol.
+//!
+//! PipeWire speaks the same protocol through `pipewire-pulse`, so this works on both servers
+//! without spawning `pactl` or `wpctl` for every dial tick.
+
+use super::audio::{AudioBackend, AudioEvent, Device, EventKind, Facility, ServerInfo, SinkInput, adjust_volume, volume_percent};
+
+use anyhow::{Result, anyhow, bail};
+use futures_util::future::{BoxFuture, FutureExt};
+use openaction::async_trait;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+
+/// Highest protocol version we know how to parse
+const PROTOCOL_VERSION: u32 = 32;
+
+const INVALID_INDEX: u32 = u32::MAX;
+const COOKIE_LENGTH: usize = 256;
+
+const COMMAND_ERROR: u32 = 0;
+const COMMAND_REPLY: u32 = 2;
+const COMMAND_AUTH: u32 = 8;
+const COMMAND_SET_CLIENT_NAME: u32 = 9;
+const COMMAND_GET_SERVER_INFO: u32 = 20;
+const COMMAND_GET_SINK_INFO: u32 = 21;
+const COMMAND_GET_SINK_INFO_LIST: u32 = 22;
+const COMMAND_GET_SOURCE_INFO: u32 = 23;
+const COMMAND_GET_SOURCE_INFO_LIST: u32 = 24;
+const COMMAND_GET_SINK_INPUT_INFO: u32 = 29;
+const COMMAND_GET_SINK_INPUT_INFO_LIST: u32 = 30;
+const COMMAND_SUBSCRIBE: u32 = 35;
+const COMMAND_SET_SINK_VOLUME: u32 = 36;
+const COMMAND_SET_SINK_INPUT_VOLUME: u32 = 37;
+const COMMAND_SET_SOURCE_VOLUME: u32 = 38;
+const COMMAND_SET_SINK_MUTE: u32 = 39;
+const COMMAND_SET_SOURCE_MUTE: u32 = 40;
+const COMMAND_SET_DEFAULT_SINK: u32 = 44;
+const COMMAND_SUBSCRIBE_EVENT: u32 = 66;
+const COMMAND_MOVE_SINK_INPUT: u32 = 67;
+const COMMAND_SET_SINK_INPUT_MUTE: u32 = 69;
+
+const SUBSCRIPTION_MASK_SINK: u32 = 0x0001;
+const SUBSCRIPTION_MASK_SOURCE: u32 = 0x0002;
+const SUBSCRIPTION_MASK_SINK_INPUT: u32 = 0x0004;
+const SUBSCRIPTION_MASK_SERVER: u32 = 0x0080;
+const SUBSCRIPTION_EVENT_FACILITY_MASK: u32 = 0x000F;
+const SUBSCRIPTION_EVENT_TYPE_MASK: u32 = 0x0030;
+
+const TAG_STRING: u8 = b't';
+const TAG_STRING_NULL: u8 = b'N';
+const TAG_U32: u8 = b'L';
+const TAG_U8: u8 = b'B';
+const TAG_U64: u8 = b'R';
+const TAG_USEC: u8 = b'U';
+const TAG_SAMPLE_SPEC: u8 = b'a';
+const TAG_ARBITRARY: u8 = b'x';
+const TAG_BOOLEAN_TRUE: u8 = b'1';
+const TAG_BOOLEAN_FALSE: u8 = b'0';
+const TAG_CHANNEL_MAP: u8 = b'm';
+const TAG_CVOLUME: u8 = b'v';
+const TAG_PROPLIST: u8 = b'P';
+const TAG_VOLUME: u8 = b'V';
+const TAG_FORMAT_INFO: u8 = b'f';
+
+#[derive(Default)]
+struct TagStructWriter {
+	data: Vec<u8>,
+}
+
+impl TagStructWriter {
+	fn u32(&mut self, value: u32) -> &mut Self {
+		self.data.push(TAG_U32);
+		self.data.extend_from_slice(&value.to_be_bytes());
+		self
+	}
+
+	fn string(&mut self, value: Option<&str>) -> &mut Self {
+		match value {
+			Some(value) => {
+				self.data.push(TAG_STRING);
+				self.data.extend_from_slice(value.as_bytes());
+				self.data.push(0);
+			}
+			None => self.data.push(TAG_STRING_NULL),
+		}
+		self
+	}
+
+	fn boolean(&mut self, value: bool) -> &mut Self {
+		self.data.push(if value { TAG_BOOLEAN_TRUE } else { TAG_BOOLEAN_FALSE });
+		self
+	}
+
+	fn arbitrary(&mut self, value: &[u8]) -> &mut Self {
+		self.data.push(TAG_ARBITRARY);
+		self.data.extend_from_slice(&(value.len() as u32).to_be_bytes());
+		self.data.extend_from_slice(value);
+		self
+	}
+
+	fn cvolume(&mut self, volume: &[u32]) -> &mut Self {
+		self.data.push(TAG_CVOLUME);
+		self.data.push(volume.len() as u8);
+		for v in volume {
+			self.data.extend_from_slice(&v.to_be_bytes());
+		}
+		self
+	}
+
+	fn proplist(&mut self, properties: &[(&str, &str)]) -> &mut Self {
+		self.data.push(TAG_PROPLIST);
+		for (key, value) in properties {
+			let mut bytes = value.as_bytes().to_vec();
+			bytes.push(0);
+			self.string(Some(key));
+			self.u32(bytes.len() as u32);
+			self.arbitrary(&bytes);
+		}
+		self.string(None)
+	}
+}
+
+struct TagStruct {
+	data: Vec<u8>,
+	pos: usize,
+}
+
+impl TagStruct {
+	fn is_empty(&self) -> bool {
+		self.pos >= self.data.len()
+	}
+
+	fn take(&mut self, len: usize) -> Result<&[u8]> {
+		if self.pos + len > self.data.len() {
+			bail!("Truncated PulseAudio packet");
+		}
+		let bytes = &self.data[self.pos..self.pos + len];
+		self.pos += len;
+		Ok(bytes)
+	}
+
+	fn tag(&mut self) -> Result<u8> {
+		Ok(self.take(1)?[0])
+	}
+
+	fn expect(&mut self, expected: u8) -> Result<()> {
+		let tag = self.tag()?;
+		if tag != expected {
+			bail!("Unexpected tag '{}' (wanted '{}')", tag as char, expected as char);
+		}
+		Ok(())
+	}
+
+	fn raw_u32(&mut self) -> Result<u32> {
+		Ok(u32::from_be_bytes(self.take(4)?.try_into()?))
+	}
+
+	fn u32(&mut self) -> Result<u32> {
+		self.expect(TAG_U32)?;
+		self.raw_u32()
+	}
+
+	fn u8(&mut self) -> Result<u8> {
+		self.expect(TAG_U8)?;
+		Ok(self.take(1)?[0])
+	}
+
+	fn u64(&mut self) -> Result<u64> {
+		let tag = self.tag()?;
+		if tag != TAG_U64 && tag != TAG_USEC {
+			bail!("Unexpected tag '{}' (wanted 64-bit value)", tag as char);
+		}
+		Ok(u64::from_be_bytes(self.take(8)?.try_into()?))
+	}
+
+	fn boolean(&mut self) -> Result<bool> {
+		match self.tag()? {
+			TAG_BOOLEAN_TRUE => Ok(true),
+			TAG_BOOLEAN_FALSE => Ok(false),
+			tag => bail!("Unexpected tag '{}' (wanted boolean)", tag as char),
+		}
+	}
+
+	fn string(&mut self) -> Result<Option<String>> {
+		match self.tag()? {
+			TAG_STRING_NULL => Ok(None),
+			TAG_STRING => {
+				let rest = &self.data[self.pos..];
+				let len = rest
+					.iter()
+					.position(|&b| b == 0)
+					.ok_or_else(|| anyhow!("Unterminated string in PulseAudio packet"))?;
+				let value = String::from_utf8_lossy(&rest[..len]).into_owned();
+				self.pos += len + 1;
+				Ok(Some(value))
+			}
+			tag => bail!("Unexpected tag '{}' (wanted string)", tag as char),
+		}
+	}
+
+	fn arbitrary(&mut self) -> Result<Vec<u8>> {
+		self.expect(TAG_ARBITRARY)?;
+		let len = self.raw_u32()? as usize;
+		Ok(self.take(len)?.to_vec())
+	}
+
+	fn sample_spec(&mut self) -> Result<()> {
+		self.expect(TAG_SAMPLE_SPEC)?;
+		self.take(6)?;
+		Ok(())
+	}
+
+	fn channel_map(&mut self) -> Result<()> {
+		self.expect(TAG_CHANNEL_MAP)?;
+		let channels = self.take(1)?[0] as usize;
+		self.take(channels)?;
+		Ok(())
+	}
+
+	fn cvolume(&mut self) -> Result<Vec<u32>> {
+		self.expect(TAG_CVOLUME)?;
+		let channels = self.take(1)?[0];
+		(0..channels).map(|_| self.raw_u32()).collect()
+	}
+
+	fn volume(&mut self) -> Result<u32> {
+		self.expect(TAG_VOLUME)?;
+		self.raw_u32()
+	}
+
+	fn proplist(&mut self) -> Result<HashMap<String, String>> {
+		self.expect(TAG_PROPLIST)?;
+		let mut properties = HashMap::new();
+		while let Some(key) = self.string()? {
+			let _len = self.u32()?;
+			let value = self.arbitrary()?;
+			let value = value.strip_suffix(&[0]).unwrap_or(&value);
+			properties.insert(key, String::from_utf8_lossy(value).into_owned());
+		}
+		Ok(properties)
+	}
+
+	fn format_info(&mut self) -> Result<()> {
+		self.expect(TAG_FORMAT_INFO)?;
+		self.u8()?;
+		self.proplist()?;
+		Ok(())
+	}
+
+	fn index(&mut self) -> Result<Option<u32>> {
+		let index = self.u32()?;
+		Ok((index != INVALID_INDEX).then_some(index))
+	}
+}
+
+fn read_sink_input(t: &mut TagStruct, version: u32) -> Result<SinkInput> {
+	let index = t.u32()?;
+	let name = t.string()?;
+	let _module = t.index()?;
+	let client = t.index()?;
+	let sink = t.u32()?;
+	t.sample_spec()?;
+	t.channel_map()?;
+	let volume = t.cvolume()?;
+	let _latency = t.u64()?;
+	let _sink_latency = t.u64()?;
+	let _resample_method = t.string()?;
+	let _driver = t.string()?;
+	let muted = if version >= 11 { t.boolean()? } else { false };
+	let properties = if version >= 13 { t.proplist()? } else { HashMap::new() };
+	let corked = if version >= 19 { t.boolean()? } else { false };
+	if version >= 20 {
+		let _has_volume = t.boolean()?;
+		let _volume_writable = t.boolean()?;
+	}
+	if version >= 21 {
+		t.format_info()?;
+	}
+	Ok(SinkInput { index, name, client, sink, volume, muted, corked, properties })
+}
+
+/// Sinks and sources share a layout; only the version that added format lists differs
+fn read_device(t: &mut TagStruct, version: u32, formats_since: u32) -> Result<Device> {
+	let index = t.u32()?;
+	let name = t.string()?.unwrap_or_default();
+	let description = t.string()?;
+	t.sample_spec()?;
+	t.channel_map()?;
+	let _module = t.index()?;
+	let volume = t.cvolume()?;
+	let muted = t.boolean()?;
+	let _monitor = t.index()?;
+	let _monitor_name = t.string()?;
+	let _latency = t.u64()?;
+	let _driver = t.string()?;
+	let _flags = t.u32()?;
+	let mut properties = HashMap::new();
+	if version >= 13 {
+		properties = t.proplist()?;
+		let _requested_latency = t.u64()?;
+	}
+	if version >= 15 {
+		let _base_volume = t.volume()?;
+		let _state = t.u32()?;
+		let _volume_steps = t.u32()?;
+		let _card = t.index()?;
+	}
+	if version >= 16 {
+		let ports = t.u32()?;
+		for _ in 0..ports {
+			let _name = t.string()?;
+			let _description = t.string()?;
+			let _priority = t.u32()?;
+			if version >= 24 {
+				let _available = t.u32()?;
+			}
+		}
+		let _active_port = t.string()?;
+	}
+	if version >= formats_since {
+		let formats = t.u8()?;
+		for _ in 0..formats {
+			t.format_info()?;
+		}
+	}
+	Ok(Device { index, name, description, volume, muted, properties })
+}
+
+fn read_event(t: &mut TagStruct) -> Result<AudioEvent> {
+	let event = t.u32()?;
+	let index = t.u32()?;
+	let facility = match event & SUBSCRIPTION_EVENT_FACILITY_MASK {
+		0 => Facility::Sink,
+		1 => Facility::Source,
+		2 => Facility::SinkInput,
+		3 => Facility::SourceOutput,
+		7 => Facility::Server,
+		_ => Facility::Other,
+	};
+	let kind = match event & SUBSCRIPTION_EVENT_TYPE_MASK {
+		0x00 => EventKind::New,
+		0x20 => EventKind::Remove,
+		_ => EventKind::Change,
+	};
+	Ok(AudioEvent { facility, kind, index })
+}
+
+fn socket_path() -> Result<PathBuf> {
+	if let Ok(server) = std::env::var("PULSE_SERVER") {
+		if let Some(path) = server.strip_prefix("unix:") {
+			return Ok(PathBuf::from(path));
+		} else if server.starts_with('/') {
+			return Ok(PathBuf::from(server));
+		}
+	}
+	if let Ok(runtime_dir) = std::env::var("XDG_RUNTIME_DIR") {
+		return Ok(PathBuf::from(runtime_dir).join("pulse/native"));
+	}
+	use std::os::unix::fs::MetadataExt;
+	let uid = std::fs::metadata("/proc/self")?.uid();
+	Ok(PathBuf::from(format!("/run/user/{}/pulse/native", uid)))
+}
+
+/// The auth cookie; pipewire-pulse ignores its contents, so zeros are fine when it is missing
+fn read_cookie() -> Vec<u8> {
+	let home = std::env::var("HOME").unwrap_or_default();
+	let candidates = [
+		std::env::var("PULSE_COOKIE").ok(),
+		std::env::var("XDG_CONFIG_HOME").ok().map(|dir| format!("{}/pulse/cookie", dir)),
+		Some(format!("{}/.config/pulse/cookie", home)),
+		Some(format!("{}/.pulse-cookie", home)),
+	];
+	candidates
+		.into_iter()
+		.flatten()
+		.filter_map(|path| std::fs::read(path).ok())
+		.find(|cookie| cookie.len() == COOKIE_LENGTH)
+		.unwrap_or_else(|| vec![0; COOKIE_LENGTH])
+}
+
+/// A single authenticated connection to the sound server
+pub struct PulseConnection {
+	stream: UnixStream,
+	next_tag: u32,
+	version: u32,
+}
+
+impl PulseConnection {
+	pub async fn connect() -> Result<Self> {
+		let path = socket_path()?;
+		let stream = UnixStream::connect(&path).await?;
+		let mut connection = PulseConnection { stream, next_tag: 0, version: PROTOCOL_VERSION };
+
+		let (tag, mut request) = connection.command(COMMAND_AUTH);
+		request.u32(PROTOCOL_VERSION).arbitrary(&read_cookie());
+		let mut reply = connection.roundtrip(tag, request).await?;
+		let server_version = reply.u32()? & 0xFFFF;
+		if server_version < 13 {
+			bail!("PulseAudio protocol version {} is too old", server_version);
+		}
+		connection.version = server_version.min(PROTOCOL_VERSION);
+
+		let (tag, mut request) = connection.command(COMMAND_SET_CLIENT_NAME);
+		request.proplist(&[("application.name", "PlayMix"), ("application.id", "PlayMix.sdPlugin")]);
+		connection.roundtrip(tag, request).await?;
+
+		log::info!("Connected to sound server at {} (protocol {})", path.display(), connection.version);
+		Ok(connection)
+	}
+
+	fn command(&mut self, command: u32) -> (u32, TagStructWriter) {
+		let tag = self.next_tag;
+		self.next_tag = self.next_tag.wrapping_add(1);
+		let mut request = TagStructWriter::default();
+		request.u32(command).u32(tag);
+		(tag, request)
+	}
+
+	async fn write_packet(&mut self, payload: &[u8]) -> Result<()> {
+		let mut packet = Vec::with_capacity(20 + payload.len());
+		packet.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+		packet.extend_from_slice(&u32::MAX.to_be_bytes());
+		packet.extend_from_slice(&[0; 12]);
+		packet.extend_from_slice(payload);
+		self.stream.write_all(&packet).await?;
+		Ok(())
+	}
+
+	async fn read_packet(&mut self) -> Result<TagStruct> {
+		loop {
+			let mut header = [0u8; 20];
+			self.stream.read_exact(&mut header).await?;
+			let length = u32::from_be_bytes(header[0..4].try_into()?) as usize;
+			let channel = u32::from_be_bytes(header[4..8].try_into()?);
+			let mut data = vec![0; length];
+			self.stream.read_exact(&mut data).await?;
+			// Anything not on the control channel is audio data we never asked for
+			if channel == u32::MAX {
+				return Ok(TagStruct { data, pos: 0 });
+			}
+		}
+	}
+
+	/// Send a request and wait for the reply carrying the same tag
+	async fn roundtrip(&mut self, tag: u32, request: TagStructWriter) -> Result<TagStruct> {
+		self.write_packet(&request.data).await?;
+		loop {
+			let mut packet = self.read_packet().await?;
+			let command = packet.u32()?;
+			let reply_tag = packet.u32()?;
+			if reply_tag != tag {
+				continue;
+			}
+			match command {
+				COMMAND_REPLY => return Ok(packet),
+				COMMAND_ERROR => bail!("Sound server returned error code {}", packet.u32()?),
+				_ => continue,
+			}
+		}
+	}
+
+	pub async fn server_info(&mut self) -> Result<ServerInfo> {
+		let (tag, request) = self.command(COMMAND_GET_SERVER_INFO);
+		let mut reply = self.roundtrip(tag, request).await?;
+		let _server_name = reply.string()?;
+		let _server_version = reply.string()?;
+		let _user_name = reply.string()?;
+		let _host_name = reply.string()?;
+		reply.sample_spec()?;
+		let default_sink = reply.string()?;
+		let default_source = reply.string()?;
+		Ok(ServerInfo { default_sink, default_source })
+	}
+
+	pub async fn sink_inputs(&mut self) -> Result<Vec<SinkInput>> {
+		let (tag, request) = self.command(COMMAND_GET_SINK_INPUT_INFO_LIST);
+		let mut reply = self.roundtrip(tag, request).await?;
+		let mut sink_inputs = Vec::new();
+		while !reply.is_empty() {
+			sink_inputs.push(read_sink_input(&mut reply, self.version)?);
+		}
+		Ok(sink_inputs)
+	}
+
+	pub async fn sink_input(&mut self, index: u32) -> Result<SinkInput> {
+		let (tag, mut request) = self.command(COMMAND_GET_SINK_INPUT_INFO);
+		request.u32(index);
+		let mut reply = self.roundtrip(tag, request).await?;
+		read_sink_input(&mut reply, self.version)
+	}
+
+	pub async fn sink_by_name(&mut self, name: &str) -> Result<Device> {
+		let (tag, mut request) = self.command(COMMAND_GET_SINK_INFO);
+		request.u32(INVALID_INDEX).string(Some(name));
+		let mut reply = self.roundtrip(tag, request).await?;
+		read_device(&mut reply, self.version, 21)
+	}
+
+	pub async fn source_by_name(&mut self, name: &str) -> Result<Device> {
+		let (tag, mut request) = self.command(COMMAND_GET_SOURCE_INFO);
+		request.u32(INVALID_INDEX).string(Some(name));
+		let mut reply = self.roundtrip(tag, request).await?;
+		read_device(&mut reply, self.version, 22)
+	}
+
+	async fn devices(&mut self, command: u32, formats_since: u32) -> Result<Vec<Device>> {
+		let (tag, request) = self.command(command);
+		let mut reply = self.roundtrip(tag, request).await?;
+		let mut devices = Vec::new();
+		while !reply.is_empty() {
+			devices.push(read_device(&mut reply, self.version, formats_since)?);
+		}
+		Ok(devices)
+	}
+
+	pub async fn sinks(&mut self) -> Result<Vec<Device>> {
+		self.devices(COMMAND_GET_SINK_INFO_LIST, 21).await
+	}
+
+	pub async fn sources(&mut self) -> Result<Vec<Device>> {
+		self.devices(COMMAND_GET_SOURCE_INFO_LIST, 22).await
+	}
+
+	pub async fn set_sink_volume(&mut self, name: &str, volume: &[u32]) -> Result<()> {
+		let (tag, mut request) = self.command(COMMAND_SET_SINK_VOLUME);
+		request.u32(INVALID_INDEX).string(Some(name)).cvolume(volume);
+		self.roundtrip(tag, request).await?;
+		Ok(())
+	}
+
+	pub async fn set_source_volume(&mut self, name: &str, volume: &[u32]) -> Result<()> {
+		let (tag, mut request) = self.command(COMMAND_SET_SOURCE_VOLUME);
+		request.u32(INVALID_INDEX).string(Some(name)).cvolume(volume);
+		self.roundtrip(tag, request).await?;
+		Ok(())
+	}
+
+	pub async fn set_sink_input_volume(&mut self, index: u32, volume: &[u32]) -> Result<()> {
+		let (tag, mut request) = self.command(COMMAND_SET_SINK_INPUT_VOLUME);
+		request.u32(index).cvolume(volume);
+		self.roundtrip(tag, request).await?;
+		Ok(())
+	}
+
+	pub async fn set_sink_mute(&mut self, name: &str, muted: bool) -> Result<()> {
+		let (tag, mut request) = self.command(COMMAND_SET_SINK_MUTE);
+		request.u32(INVALID_INDEX).string(Some(name)).boolean(muted);
+		self.roundtrip(tag, request).await?;
+		Ok(())
+	}
+
+	pub async fn set_source_mute(&mut self, name: &str, muted: bool) -> Result<()> {
+		let (tag, mut request) = self.command(COMMAND_SET_SOURCE_MUTE);
+		request.u32(INVALID_INDEX).string(Some(name)).boolean(muted);
+		self.roundtrip(tag, request).await?;
+		Ok(())
+	}
+
+	pub async fn set_sink_input_mute(&mut self, index: u32, muted: bool) -> Result<()> {
+		let (tag, mut request) = self.command(COMMAND_SET_SINK_INPUT_MUTE);
+		request.u32(index).boolean(muted);
+		self.roundtrip(tag, request).await?;
+		Ok(())
+	}
+
+	/// Ask for change notifications; afterwards read them with [`Self::next_event`]
+	pub async fn subscribe(&mut self, mask: u32) -> Result<()> {
+		let (tag, mut request) = self.command(COMMAND_SUBSCRIBE);
+		request.u32(mask);
+		self.roundtrip(tag, request).await?;
+		Ok(())
+	}
+
+	pub async fn next_event(&mut self) -> Result<AudioEvent> {
+		loop {
+			let mut packet = self.read_packet().await?;
+			if packet.u32()? == COMMAND_SUBSCRIBE_EVENT {
+				let _tag = packet.u32()?;
+				return read_event(&mut packet);
+			}
+		}
+	}
+
+	pub async fn move_sink_input(&mut self, index: u32, sink: &str) -> Result<()> {
+		let (tag, mut request) = self.command(COMMAND_MOVE_SINK_INPUT);
+		request.u32(index).u32(INVALID_INDEX).string(Some(sink));
+		self.roundtrip(tag, request).await?;
+		Ok(())
+	}
+
+	pub async fn set_default_sink(&mut self, name: &str) -> Result<()> {
+		let (tag, mut request) = self.command(COMMAND_SET_DEFAULT_SINK);
+		request.string(Some(name));
+		self.roundtrip(tag, request).await?;
+		Ok(())
+	}
+}
+
+/// Lazily connected, long-lived client shared by every action
+#[derive(Default)]
+pub struct PulseClient {
+	connection: tokio::sync::Mutex<Option<PulseConnection>>,
+}
+
+impl PulseClient {
+	/// Run `f` on the shared connection, reconnecting first if needed.
+	/// I/O failures drop the connection so the next call starts fresh.
+	async fn with_connection<T>(
+		&self,
+		f: impl for<'a> FnOnce(&'a mut PulseConnection) -> BoxFuture<'a, Result<T>>,
+	) -> Result<T> {
+		let mut guard = self.connection.lock().await;
+		if guard.is_none() {
+			*guard = Some(PulseConnection::connect().await?);
+		}
+		let result = f(guard.as_mut().expect("connection was just established")).await;
+		if let Err(error) = &result
+			&& error.downcast_ref::<std::io::Error>().is_some()
+		{
+			log::warn!("Lost connection to sound server: {}", error);
+			*guard = None;
+		}
+		result
+	}
+}
+
+#[async_trait]
+impl AudioBackend for PulseClient {
+	async fn server_info(&self) -> Result<ServerInfo> {
+		self.with_connection(|c| c.server_info().boxed()).await
+	}
+
+	async fn sink_inputs(&self) -> Result<Vec<SinkInput>> {
+		self.with_connection(|c| c.sink_inputs().boxed()).await
+	}
+
+	async fn sink_input(&self, index: u32) -> Result<SinkInput> {
+		self.with_connection(|c| c.sink_input(index).boxed()).await
+	}
+
+	async fn set_sink_input_volume(&self, index: u32, volume: &[u32]) -> Result<()> {
+		let volume = volume.to_vec();
+		self.with_connection(|c| async move { c.set_sink_input_volume(index, &volume).await }.boxed())
+			.await
+	}
+
+	async fn set_sink_input_mute(&self, index: u32, muted: bool) -> Result<()> {
+		self.with_connection(|c| c.set_sink_input_mute(index, muted).boxed()).await
+	}
+
+	async fn sinks(&self) -> Result<Vec<Device>> {
+		self.with_connection(|c| c.sinks().boxed()).await
+	}
+
+	async fn sources(&self) -> Result<Vec<Device>> {
+		self.with_connection(|c| c.sources().boxed()).await
+	}
+
+	async fn sink(&self, name: &str) -> Result<Device> {
+		let name = name.to_owned();
+		self.with_connection(|c| async move { c.sink_by_name(&name).await }.boxed())
+			.await
+	}
+
+	async fn set_sink_volume(&self, name: &str, volume: &[u32]) -> Result<()> {
+		let (name, volume) = (name.to_owned(), volume.to_vec());
+		self.with_connection(|c| async move { c.set_sink_volume(&name, &volume).await }.boxed())
+			.await
+	}
+
+	async fn set_sink_mute(&self, name: &str, muted: bool) -> Result<()> {
+		let name = name.to_owned();
+		self.with_connection(|c| async move { c.set_sink_mute(&name, muted).await }.boxed())
+			.await
+	}
+
+	async fn move_sink_input(&self, index: u32, sink: &str) -> Result<()> {
+		let sink = sink.to_owned();
+		self.with_connection(|c| async move { c.move_sink_input(index, &sink).await }.boxed())
+			.await
+	}
+
+	async fn set_default_sink(&self, name: &str) -> Result<()> {
+		let name = name.to_owned();
+		self.with_connection(|c| async move { c.set_default_sink(&name).await }.boxed())
+			.await
+	}
+
+	async fn source(&self, name: &str) -> Result<Device> {
+		let name = name.to_owned();
+		self.with_connection(|c| async move { c.source_by_name(&name).await }.boxed())
+			.await
+	}
+
+	async fn set_source_volume(&self, name: &str, volume: &[u32]) -> Result<()> {
+		let (name, volume) = (name.to_owned(), volume.to_vec());
+		self.with_connection(|c| async move { c.set_source_volume(&name, &volume).await }.boxed())
+			.await
+	}
+
+	async fn set_source_mute(&self, name: &str, muted: bool) -> Result<()> {
+		let name = name.to_owned();
+		self.with_connection(|c| async move { c.set_source_mute(&name, muted).await }.boxed())
+			.await
+	}
+
+	/// Events arrive on a dedicated connection so they never interleave with replies
+	async fn subscribe(&self) -> Result<UnboundedReceiver<AudioEvent>> {
+		let mut connection = PulseConnection::connect().await?;
+		connection
+			.subscribe(
+				SUBSCRIPTION_MASK_SINK
+					| SUBSCRIPTION_MASK_SOURCE
+					| SUBSCRIPTION_MASK_SINK_INPUT
+					| SUBSCRIPTION_MASK_SERVER,
+			)
+			.await?;
+
+		let (sender, receiver) = unbounded_channel();
+		tokio::spawn(async move {
+			loop {
+				match connection.next_event().await {
+					Ok(event) => {
+						if sender.send(event).is_err() {
+							break;
+						}
+					}
+					Err(error) => {
+						log::warn!("Sound server event stream ended: {}", error);
+						break;
+					}
+				}
+			}
+		});
+		Ok(receiver)
+	}
+
+	/// Read and write under one lock so fast spins can't interleave
+	async fn change_sink_input_volume(&self, index: u32, delta_percent: i32, limit_percent: Option<u32>) -> Result<u32> {
+		self.with_connection(|c| {
+			async move {
+				let sink_input = c.sink_input(index).await?;
+				let volume = adjust_volume(&sink_input.volume, delta_percent, limit_percent);
+				c.set_sink_input_volume(index, &volume).await?;
+				Ok(volume_percent(&volume))
+			}
+			.boxed()
+		})
+		.await
+	}
+
+	async fn change_sink_volume(&self, name: &str, delta_percent: i32, limit_percent: Option<u32>) -> Result<u32> {
+		let name = name.to_owned();
+		self.with_connection(|c| {
+			async move {
+				let sink = c.sink_by_name(&name).await?;
+				let volume = adjust_volume(&sink.volume, delta_percent, limit_percent);
+				c.set_sink_volume(&name, &volume).await?;
+				Ok(volume_percent(&volume))
+			}
+			.boxed()
+		})
+		.await
+	}
+
+	async fn change_source_volume(&self, name: &str, delta_percent: i32, limit_percent: Option<u32>) -> Result<u32> {
+		let name = name.to_owned();
+		self.with_connection(|c| {
+			async move {
+				let source = c.source_by_name(&name).await?;
+				let volume = adjust_volume(&source.volume, delta_percent, limit_percent);
+				c.set_source_volume(&name, &volume).await?;
+				Ok(volume_percent(&volume))
+			}
+			.boxed()
+		})
+		.await
+	}
+}